@@ -0,0 +1,334 @@
+use anyhow::{anyhow, Error, Result};
+use std::fmt::{self, Display, Formatter};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// The symbols a balanced base is written with. The base is always odd, one more than twice
+/// the number of negative digits.
+pub trait Digits {
+    /// Symbols for the digits 0, 1, 2, ... up to half the base
+    const POSITIVE: &'static [char];
+    /// Symbols for the digits -1, -2, ... down to minus half the base
+    const NEGATIVE: &'static [char];
+}
+
+/// Balanced quinary as used by the fuel bob: `=` is -2, `-` is -1.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Default)]
+pub struct SnafuDigits;
+
+impl Digits for SnafuDigits {
+    const POSITIVE: &'static [char] = &['0', '1', '2'];
+    const NEGATIVE: &'static [char] = &['-', '='];
+}
+
+/// Balanced ternary, with `T` for -1.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Default)]
+pub struct TernaryDigits;
+
+impl Digits for TernaryDigits {
+    const POSITIVE: &'static [char] = &['0', '1'];
+    const NEGATIVE: &'static [char] = &['T'];
+}
+
+pub type Snafu = Balanced<SnafuDigits>;
+pub type Ternary = Balanced<TernaryDigits>;
+
+/// An integer of any size in a balanced base, where each digit is between minus and plus half
+/// the base. Arithmetic works on the digits directly, so there is no limit on the magnitude.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Default)]
+pub struct Balanced<D> {
+    /// Least significant first, without zeros at the most significant end
+    digits: Vec<i8>,
+    symbols: PhantomData<D>,
+}
+
+impl<D: Digits> Balanced<D> {
+    pub fn base() -> i64 {
+        2 * D::NEGATIVE.len() as i64 + 1
+    }
+
+    fn half() -> i64 {
+        D::NEGATIVE.len() as i64
+    }
+
+    pub fn zero() -> Self {
+        Self::from_digits(Vec::new())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// -1, 0 or 1, the sign of the most significant digit.
+    pub fn signum(&self) -> i64 {
+        self.digits.last().map_or(0, |&digit| digit.signum() as i64)
+    }
+
+    /// Build a number from arbitrarily large digit values, least significant first, carrying
+    /// whatever is out of range into the next digit.
+    fn normalize(mut values: Vec<i64>) -> Self {
+        let (base, half) = (Self::base(), Self::half());
+        let mut digits = Vec::with_capacity(values.len() + 1);
+        let mut carry = 0;
+        let mut i = 0;
+        while i < values.len() || carry != 0 {
+            let value = values.get_mut(i).map_or(0, std::mem::take) + carry;
+            carry = (value + half).div_euclid(base);
+            digits.push((value - carry * base) as i8);
+            i += 1;
+        }
+        Self::from_digits(digits)
+    }
+
+    fn from_digits(mut digits: Vec<i8>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Self {
+            digits,
+            symbols: PhantomData,
+        }
+    }
+
+    fn symbol(digit: i8) -> char {
+        if digit < 0 {
+            D::NEGATIVE[(-digit) as usize - 1]
+        } else {
+            D::POSITIVE[digit as usize]
+        }
+    }
+
+    fn digit(symbol: char) -> Option<i8> {
+        if let Some(pos) = D::POSITIVE.iter().position(|&ch| ch == symbol) {
+            return Some(pos as i8);
+        }
+        let neg = D::NEGATIVE.iter().position(|&ch| ch == symbol)?;
+        Some(-(neg as i8) - 1)
+    }
+}
+
+impl<D: Digits> FromStr for Balanced<D> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(anyhow!("Empty number"));
+        }
+        let digits = s
+            .chars()
+            .rev()
+            .enumerate()
+            .map(|(from_end, ch)| {
+                Self::digit(ch).ok_or_else(|| {
+                    let column = s.chars().count() - from_end;
+                    anyhow!("Unknown digit {ch:?} at column {column} of {s:?}")
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self::from_digits(digits))
+    }
+}
+
+impl<D: Digits> Display for Balanced<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "{}", Self::symbol(0));
+        }
+        let text: String = self.digits.iter().rev().map(|&d| Self::symbol(d)).collect();
+        f.pad(&text)
+    }
+}
+
+impl<D: Digits> From<i128> for Balanced<D> {
+    fn from(mut value: i128) -> Self {
+        let (base, half) = (Self::base() as i128, Self::half() as i128);
+        let mut digits = Vec::new();
+        while value != 0 {
+            let (mut digit, mut rest) = (value.rem_euclid(base), value.div_euclid(base));
+            // Digits past the half way point borrow from the next one up instead
+            if digit > half {
+                digit -= base;
+                rest += 1;
+            }
+            digits.push(digit as i8);
+            value = rest;
+        }
+        Self::from_digits(digits)
+    }
+}
+
+impl<D: Digits> From<i64> for Balanced<D> {
+    fn from(value: i64) -> Self {
+        Self::from(value as i128)
+    }
+}
+
+impl<D: Digits> TryFrom<&Balanced<D>> for i128 {
+    type Error = Error;
+
+    fn try_from(number: &Balanced<D>) -> Result<i128> {
+        let base = Balanced::<D>::base() as i128;
+        number
+            .digits
+            .iter()
+            .rev()
+            .try_fold(0i128, |acc, &digit| {
+                acc.checked_mul(base)?.checked_add(digit as i128)
+            })
+            .ok_or_else(|| anyhow!("{number} doesn't fit in an i128"))
+    }
+}
+
+impl<D: Digits> TryFrom<&Balanced<D>> for i64 {
+    type Error = Error;
+
+    fn try_from(number: &Balanced<D>) -> Result<i64> {
+        let wide = i128::try_from(number)?;
+        i64::try_from(wide).map_err(|_| anyhow!("{number} doesn't fit in an i64"))
+    }
+}
+
+impl<D: Digits> Neg for &Balanced<D> {
+    type Output = Balanced<D>;
+
+    fn neg(self) -> Balanced<D> {
+        Balanced::from_digits(self.digits.iter().map(|&digit| -digit).collect())
+    }
+}
+
+impl<D: Digits> Neg for Balanced<D> {
+    type Output = Balanced<D>;
+
+    fn neg(self) -> Balanced<D> {
+        -&self
+    }
+}
+
+impl<D: Digits> Add for &Balanced<D> {
+    type Output = Balanced<D>;
+
+    fn add(self, rhs: Self) -> Balanced<D> {
+        let len = self.digits.len().max(rhs.digits.len());
+        let digit = |digits: &[i8], i: usize| digits.get(i).copied().unwrap_or(0) as i64;
+        let sums = (0..len)
+            .map(|i| digit(&self.digits, i) + digit(&rhs.digits, i))
+            .collect();
+        Balanced::normalize(sums)
+    }
+}
+
+impl<D: Digits> Sub for &Balanced<D> {
+    type Output = Balanced<D>;
+
+    fn sub(self, rhs: Self) -> Balanced<D> {
+        self + &-rhs
+    }
+}
+
+impl<D: Digits> Mul for &Balanced<D> {
+    type Output = Balanced<D>;
+
+    fn mul(self, rhs: Self) -> Balanced<D> {
+        if self.is_zero() || rhs.is_zero() {
+            return Balanced::zero();
+        }
+        // Long multiplication, leaving the carrying until the end
+        let mut products = vec![0i64; self.digits.len() + rhs.digits.len()];
+        for (i, &left) in self.digits.iter().enumerate() {
+            for (j, &right) in rhs.digits.iter().enumerate() {
+                products[i + j] += left as i64 * right as i64;
+            }
+        }
+        Balanced::normalize(products)
+    }
+}
+
+macro_rules! by_value {
+    ($($op:ident $method:ident),*) => {$(
+        impl<D: Digits> $op for Balanced<D> {
+            type Output = Balanced<D>;
+
+            fn $method(self, rhs: Self) -> Balanced<D> {
+                (&self).$method(&rhs)
+            }
+        }
+    )*};
+}
+
+by_value!(Add add, Sub sub, Mul mul);
+
+impl<D: Digits> Sum for Balanced<D> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, number| &acc + &number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Snafu, Ternary};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_examples() {
+        for (text, value) in [
+            ("1=-0-2", 1747i64),
+            ("2=01", 201),
+            ("1121-1110-1=0", 314159265),
+        ] {
+            let snafu: Snafu = text.parse().unwrap();
+            assert_eq!(i64::try_from(&snafu).unwrap(), value);
+            assert_eq!(Snafu::from(value).to_string(), text);
+        }
+        assert_eq!(Snafu::zero().to_string(), "0");
+        assert_eq!("000".parse::<Snafu>().unwrap(), Snafu::zero());
+        assert_eq!(Ternary::from(-5i64).to_string(), "T11");
+        assert_eq!(Snafu::base(), 5);
+        assert_eq!(Ternary::base(), 3);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = "12x=".parse::<Snafu>().unwrap_err();
+        assert_eq!(err.to_string(), "Unknown digit 'x' at column 3 of \"12x=\"");
+        assert!("".parse::<Snafu>().is_err());
+        assert!("-".parse::<Ternary>().is_err());
+        let huge: Snafu = "2".repeat(50).parse().unwrap();
+        assert!(i64::try_from(&huge).is_err());
+        assert!(i128::try_from(&huge).is_ok());
+    }
+
+    #[test]
+    fn test_beyond_i128() {
+        let big = Snafu::from(i128::MAX);
+        let square = &big * &big;
+        assert!(i128::try_from(&square).is_err());
+        assert_eq!(&(&square - &big) + &big, square);
+        assert_eq!(i128::try_from(&(&square - &square)).unwrap(), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(value in any::<i64>()) {
+            let snafu = Snafu::from(value);
+            prop_assert_eq!(i64::try_from(&snafu).unwrap(), value);
+            prop_assert_eq!(snafu.to_string().parse::<Snafu>().unwrap(), snafu.clone());
+            prop_assert_eq!(snafu.signum(), value.signum());
+            let ternary = Ternary::from(value);
+            prop_assert_eq!(i64::try_from(&ternary).unwrap(), value);
+        }
+
+        #[test]
+        fn prop_arithmetic(a in any::<i64>(), b in any::<i64>()) {
+            let (wa, wb) = (a as i128, b as i128);
+            let (sa, sb) = (Snafu::from(a), Snafu::from(b));
+            prop_assert_eq!(i128::try_from(&(&sa + &sb)).unwrap(), wa + wb);
+            prop_assert_eq!(i128::try_from(&(&sa - &sb)).unwrap(), wa - wb);
+            prop_assert_eq!(i128::try_from(&(&sa * &sb)).unwrap(), wa * wb);
+            let (ta, tb) = (Ternary::from(a), Ternary::from(b));
+            prop_assert_eq!(i128::try_from(&(ta.clone() + tb.clone())).unwrap(), wa + wb);
+            prop_assert_eq!(i128::try_from(&(ta * tb)).unwrap(), wa * wb);
+        }
+    }
+}
@@ -1,124 +1,321 @@
 use anyhow::{anyhow, Context, Result};
 use aoc::io::read_stdin;
 use itertools::Itertools;
-use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
-pub enum Hand {
-    Rock,
-    Paper,
-    Scissors,
+pub enum Outcome {
+    Lose,
+    Draw,
+    Win,
 }
 
-impl PartialOrd<Self> for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Outcome::Lose => "lose",
+            Outcome::Draw => "draw",
+            Outcome::Win => "win",
+        };
+        f.write_str(name)
     }
 }
 
-impl TryFrom<char> for Hand {
+impl TryFrom<&str> for Outcome {
     type Error = anyhow::Error;
 
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        use Hand::*;
-        match value {
-            'A' | 'X' => Ok(Rock),
-            'B' | 'Y' => Ok(Paper),
-            'C' | 'Z' => Ok(Scissors),
-            _ => Err(anyhow!("Illegal hand: {value}")),
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        use Outcome::*;
+        match value.to_ascii_lowercase().as_str() {
+            "x" | "lose" => Ok(Lose),
+            "y" | "draw" => Ok(Draw),
+            "z" | "win" => Ok(Win),
+            _ => Err(anyhow!("Illegal outcome: {value}")),
         }
     }
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        use Hand::*;
-        use Ordering::*;
-        match (self, other) {
-            (Rock, Scissors) => Greater,
-            (Paper, Rock) => Greater,
-            (Scissors, Paper) => Greater,
-            (Rock, Rock) => Equal,
-            (Paper, Paper) => Equal,
-            (Scissors, Scissors) => Equal,
-            (Scissors, Rock) => Less,
-            (Paper, Scissors) => Less,
-            (Rock, Paper) => Less,
+/// A hand, as its position in the game's list of hands
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Hand(usize);
+
+/// A game in the rock paper scissors family, where every pair of different hands has a winner
+pub struct Game {
+    names: Vec<String>,
+    /// `beats[a][b]` says whether `a` beats `b`
+    beats: Vec<Vec<bool>>,
+}
+
+impl Game {
+    /// Set up a game from its hands and which beats which, checking that every pair of
+    /// different hands is decided exactly one way.
+    fn new(names: &[&str], wins: &[(&str, &str)]) -> Result<Game> {
+        if names.len() > 26 {
+            return Err(anyhow!("At most 26 hands fit in a strategy guide"));
+        }
+        if let Some(name) = names.iter().duplicates().next() {
+            return Err(anyhow!("{name} appears twice"));
         }
+        let mut game = Game {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            beats: vec![vec![false; names.len()]; names.len()],
+        };
+        for &(winner, loser) in wins {
+            let (Hand(w), Hand(l)) = (game.by_name(winner)?, game.by_name(loser)?);
+            if w == l {
+                return Err(anyhow!("{winner} can't beat itself"));
+            }
+            if game.beats[l][w] {
+                return Err(anyhow!("{winner} both beats and loses to {loser}"));
+            }
+            game.beats[w][l] = true;
+        }
+        for (a, b) in (0..names.len()).tuple_combinations() {
+            if !game.beats[a][b] && !game.beats[b][a] {
+                return Err(anyhow!("Nothing decides {} against {}", names[a], names[b]));
+            }
+        }
+        Ok(game)
+    }
+
+    fn classic() -> Game {
+        Game::new(
+            &["Rock", "Paper", "Scissors"],
+            &[
+                ("Rock", "Scissors"),
+                ("Paper", "Rock"),
+                ("Scissors", "Paper"),
+            ],
+        )
+        .expect("Rock paper scissors is well defined")
     }
-}
 
-impl Hand {
-    fn points(&self) -> u32 {
-        use Hand::*;
+    fn lizard_spock() -> Game {
+        Game::new(
+            &["Rock", "Paper", "Scissors", "Lizard", "Spock"],
+            &[
+                ("Scissors", "Paper"),
+                ("Paper", "Rock"),
+                ("Rock", "Lizard"),
+                ("Lizard", "Spock"),
+                ("Spock", "Scissors"),
+                ("Scissors", "Lizard"),
+                ("Lizard", "Paper"),
+                ("Paper", "Spock"),
+                ("Spock", "Rock"),
+                ("Rock", "Scissors"),
+            ],
+        )
+        .expect("Rock paper scissors lizard Spock is well defined")
+    }
 
-        match self {
-            Rock => 1,
-            Paper => 2,
-            Scissors => 3,
+    fn hands(&self) -> impl Iterator<Item = Hand> {
+        (0..self.names.len()).map(Hand)
+    }
+
+    fn name(&self, hand: Hand) -> &str {
+        &self.names[hand.0]
+    }
+
+    fn by_name(&self, name: &str) -> Result<Hand> {
+        self.names
+            .iter()
+            .position(|known| known.eq_ignore_ascii_case(name))
+            .map(Hand)
+            .ok_or_else(|| anyhow!("Illegal hand: {name}"))
+    }
+
+    fn outcome(&self, mine: Hand, theirs: Hand) -> Outcome {
+        if self.beats[mine.0][theirs.0] {
+            Outcome::Win
+        } else if self.beats[theirs.0][mine.0] {
+            Outcome::Lose
+        } else {
+            Outcome::Draw
         }
     }
+
+    /// Read a hand from a column of the guide, either by name or by letter. The opponent's
+    /// column counts from `A` and ours from `X`, wrapping around to `A` after `Z`.
+    fn hand(&self, token: &str, column: usize) -> Result<Hand> {
+        let mut chars = token.chars();
+        if let (Some(letter @ 'A'..='Z'), None) = (chars.next(), chars.next()) {
+            let first = if column == 0 { b'A' } else { b'X' };
+            let index = (letter as u8 + 26 - first) % 26;
+            return match index as usize {
+                index if index < self.names.len() => Ok(Hand(index)),
+                _ => Err(anyhow!("Illegal hand: {letter}")),
+            };
+        }
+        self.by_name(token)
+    }
+}
+
+/// Points for the hand played and for the outcome of a round
+pub struct Scoring {
+    /// Points for each hand of the game, in order
+    hands: Vec<u32>,
+    /// Points for losing, drawing and winning
+    outcomes: [u32; 3],
 }
 
-fn points(duel: &(Hand, Hand)) -> u32 {
-    use Ordering::*;
-    let (theirs, my) = duel;
-    my.points()
-        + match my.cmp(theirs) {
-            Less => 0,
-            Equal => 3,
-            Greater => 6,
+impl Scoring {
+    /// The puzzle's scoring: 1 for the first hand, 2 for the second and so on, and 0, 3 or 6
+    /// for losing, drawing or winning
+    fn standard(game: &Game) -> Scoring {
+        Scoring {
+            hands: (1..=game.names.len() as u32).collect(),
+            outcomes: [0, 3, 6],
         }
+    }
+
+    fn points(&self, game: &Game, mine: Hand, theirs: Hand) -> u32 {
+        self.hands[mine.0] + self.outcomes[game.outcome(mine, theirs) as usize]
+    }
 }
 
-fn parse_duel(line: &str) -> Result<(Hand, Hand)> {
-    let parts = line.split_ascii_whitespace().collect_vec();
-    let my = parts[0].chars().next().context("Empty hand")?.try_into()?;
-    let theirs = parts[1].chars().next().context("Empty hand")?.try_into()?;
-    Ok((my, theirs))
-}
-
-fn part1(inp: &str) -> Result<u32> {
-    let results: Result<Vec<_>> = inp
-        .lines()
-        .filter(|line| !line.is_empty())
-        .map(parse_duel)
-        .collect();
-    results.map(|duels| duels.iter().map(points).sum())
-}
-
-fn part2(inp: &str) -> Result<u32> {
-    let results: Result<Vec<_>> = inp
-        .lines()
-        .filter(|line| !line.is_empty())
-        .map(parse_duel)
-        .collect();
-    let points = results?
-        .iter()
-        .map(|duel| {
-            use Hand::*;
-            use Ordering::*;
-            let (their_choice, my_condition) = duel;
-            let desired_outcome = match my_condition {
-                Rock => Less,        // X
-                Paper => Equal,      // Y
-                Scissors => Greater, // Z
+/// How to read the second column of the strategy guide
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Reading {
+    /// The hand to play
+    Hands,
+    /// The outcome to aim for
+    Outcomes,
+}
+
+/// One round of the guide, played as told and as well as possible
+pub struct Round {
+    theirs: Hand,
+    mine: Hand,
+    outcome: Outcome,
+    points: u32,
+    best: Hand,
+    best_points: u32,
+}
+
+/// The best scoring hand against `theirs`, out of those that give `outcome` if there is one
+fn choose(game: &Game, scoring: &Scoring, theirs: Hand, outcome: Option<Outcome>) -> Option<Hand> {
+    game.hands()
+        .filter(|&mine| outcome.is_none_or(|outcome| game.outcome(mine, theirs) == outcome))
+        .max_by_key(|&mine| scoring.points(game, mine, theirs))
+}
+
+fn parse_guide(inp: &str) -> Result<Vec<(&str, &str)>> {
+    inp.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            line.split_ascii_whitespace()
+                .collect_tuple()
+                .with_context(|| format!("line {}: Expected two columns", i + 1))
+        })
+        .collect()
+}
+
+/// Play through the guide, reading its second column as told
+fn play(game: &Game, scoring: &Scoring, inp: &str, reading: Reading) -> Result<Vec<Round>> {
+    parse_guide(inp)?
+        .into_iter()
+        .map(|(theirs, second)| -> Result<Round> {
+            let theirs = game.hand(theirs, 0)?;
+            let mine = match reading {
+                Reading::Hands => game.hand(second, 1)?,
+                Reading::Outcomes => {
+                    let outcome = Outcome::try_from(second)?;
+                    choose(game, scoring, theirs, Some(outcome))
+                        .with_context(|| format!("No way to {outcome}"))?
+                }
             };
-            let options = [Rock, Paper, Scissors];
-            let my_choice = options
-                .iter()
-                .find(|&hand| hand.cmp(their_choice) == desired_outcome)
-                .unwrap();
-            points(&(*their_choice, *my_choice))
+            let best = choose(game, scoring, theirs, None).context("No hands")?;
+            Ok(Round {
+                theirs,
+                mine,
+                outcome: game.outcome(mine, theirs),
+                points: scoring.points(game, mine, theirs),
+                best,
+                best_points: scoring.points(game, best, theirs),
+            })
         })
-        .sum();
-    Ok(points)
+        .enumerate()
+        .map(|(i, round)| round.with_context(|| format!("round {}", i + 1)))
+        .collect()
+}
+
+/// One line per round, then the total against the best possible total
+fn analysis(game: &Game, rounds: &[Round]) -> String {
+    let mut out = String::new();
+    for (i, round) in rounds.iter().enumerate() {
+        out += &format!(
+            "{}: {} against {}: {}, {} points (best {} for {})\n",
+            i + 1,
+            game.name(round.mine),
+            game.name(round.theirs),
+            round.outcome,
+            round.points,
+            game.name(round.best),
+            round.best_points
+        );
+    }
+    let total: u32 = rounds.iter().map(|round| round.points).sum();
+    let best: u32 = rounds.iter().map(|round| round.best_points).sum();
+    out += &format!("Total {total}, best possible {best}\n");
+    out
+}
+
+fn total(game: &Game, scoring: &Scoring, inp: &str, reading: Reading) -> Result<u32> {
+    let rounds = play(game, scoring, inp, reading)?;
+    Ok(rounds.iter().map(|round| round.points).sum())
+}
+
+fn part1(game: &Game, scoring: &Scoring, inp: &str) -> Result<u32> {
+    total(game, scoring, inp, Reading::Hands)
+}
+
+fn part2(game: &Game, scoring: &Scoring, inp: &str) -> Result<u32> {
+    total(game, scoring, inp, Reading::Outcomes)
+}
+
+fn parse_points(value: &str) -> Result<Vec<u32>> {
+    value.split(',').map(|n| Ok(n.parse()?)).collect()
 }
 
 fn main() -> Result<()> {
+    let mut game = Game::classic();
+    let mut hands = None;
+    let mut outcomes = None;
+    let mut analyse = false;
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("game", "rps")) => game = Game::classic(),
+            Some(("game", "rpsls")) => game = Game::lizard_spock(),
+            Some(("hands", value)) => hands = Some(parse_points(value)?),
+            Some(("outcomes", value)) => outcomes = Some(parse_points(value)?),
+            None if arg == "analyse" => analyse = true,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
+    let mut scoring = Scoring::standard(&game);
+    if let Some(hands) = hands {
+        if hands.len() != game.names.len() {
+            return Err(anyhow!("Need points for {} hands", game.names.len()));
+        }
+        scoring.hands = hands;
+    }
+    if let Some(outcomes) = outcomes {
+        scoring.outcomes = outcomes
+            .try_into()
+            .map_err(|_| anyhow!("Need points for losing, drawing and winning"))?;
+    }
     let inp = read_stdin()?;
-    let p1 = part1(inp.as_str())?;
-    let p2 = part2(inp.as_str())?;
+    if analyse {
+        for reading in [Reading::Hands, Reading::Outcomes] {
+            let rounds = play(&game, &scoring, inp.as_str(), reading)?;
+            print!("{}", analysis(&game, &rounds));
+        }
+        return Ok(());
+    }
+    let p1 = part1(&game, &scoring, inp.as_str())?;
+    let p2 = part2(&game, &scoring, inp.as_str())?;
     println!("{p1}");
     println!("{p2}");
     Ok(())
@@ -133,13 +330,93 @@ B X
 C Z
 ";
 
+    fn classic(part: fn(&Game, &Scoring, &str) -> Result<u32>, inp: &str) -> Result<u32> {
+        let game = Game::classic();
+        part(&game, &Scoring::standard(&game), inp)
+    }
+
     #[test]
     fn part1_test() {
-        assert_eq!(part1(EXAMPLE).unwrap(), 15);
+        assert_eq!(classic(part1, EXAMPLE).unwrap(), 15);
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(part2(EXAMPLE).unwrap(), 12);
+        assert_eq!(classic(part2, EXAMPLE).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_games() {
+        let game = Game::lizard_spock();
+        let (rock, spock) = (
+            game.by_name("rock").unwrap(),
+            game.by_name("Spock").unwrap(),
+        );
+        assert_eq!(game.outcome(spock, rock), Outcome::Win);
+        assert_eq!(game.outcome(rock, spock), Outcome::Lose);
+        assert_eq!(game.outcome(rock, rock), Outcome::Draw);
+        // Every hand beats exactly two others
+        for hand in game.hands() {
+            let wins = game
+                .hands()
+                .filter(|&other| game.outcome(hand, other) == Outcome::Win);
+            assert_eq!(wins.count(), 2);
+        }
+        assert_eq!(game.hand("E", 0).unwrap(), spock);
+        assert_eq!(game.hand("B", 1).unwrap(), spock);
+        assert_eq!(game.hand("X", 1).unwrap(), rock);
+        assert!(game.hand("F", 0).is_err());
+
+        let err = |wins: &[(&str, &str)]| Game::new(&["A", "B", "C"], wins).err().unwrap();
+        assert_eq!(
+            err(&[("A", "B"), ("B", "C")]).to_string(),
+            "Nothing decides A against C"
+        );
+        assert_eq!(
+            err(&[("A", "B"), ("B", "A")]).to_string(),
+            "B both beats and loses to A"
+        );
+        assert_eq!(err(&[("A", "A")]).to_string(), "A can't beat itself");
+        assert_eq!(err(&[("A", "D")]).to_string(), "Illegal hand: D");
+    }
+
+    #[test]
+    fn test_analysis() {
+        let game = Game::classic();
+        let scoring = Scoring::standard(&game);
+        let rounds = play(&game, &scoring, EXAMPLE, Reading::Hands).unwrap();
+        assert_eq!(
+            analysis(&game, &rounds),
+            "1: Paper against Rock: win, 8 points (best Paper for 8)
+2: Rock against Paper: lose, 1 points (best Scissors for 9)
+3: Scissors against Scissors: draw, 6 points (best Rock for 7)
+Total 15, best possible 24
+"
+        );
+
+        // Where losing costs nothing extra, the cheapest losing hand is still the best loss
+        let scoring = Scoring {
+            hands: vec![5, 1, 1],
+            outcomes: [0, 1, 2],
+        };
+        let rounds = play(&game, &scoring, "A X\nC X\n", Reading::Outcomes).unwrap();
+        assert_eq!(game.name(rounds[0].mine), "Scissors");
+        assert_eq!(game.name(rounds[0].best), "Rock");
+        assert_eq!(rounds[1].outcome, Outcome::Lose);
+
+        let game = Game::lizard_spock();
+        let scoring = Scoring::standard(&game);
+        let rounds = play(&game, &scoring, "E Z\n", Reading::Outcomes).unwrap();
+        // Both Paper and Lizard beat Spock, and Lizard is worth more
+        assert_eq!(game.name(rounds[0].mine), "Lizard");
+        assert_eq!(rounds[0].points, 10);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = classic(part1, "A Y\nB\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: Expected two columns");
+        let err = classic(part2, "A Y\nD X\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "round 2: Illegal hand: D");
     }
 }
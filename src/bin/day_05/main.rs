@@ -1,5 +1,9 @@
-use std::io;
-use std::iter::Iterator;
+use anyhow::{anyhow, Context, Result};
+use aoc::io::read_stdin;
+use std::fmt::{self, Display, Formatter};
+
+/// Each stack from the bottom up
+type Stacks = Vec<Vec<char>>;
 
 pub struct Instruction {
     source: usize,
@@ -7,156 +11,294 @@ pub struct Instruction {
     count: usize,
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count, self.source, self.dest
+        )
+    }
+}
+
 pub struct Problem {
-    state: Vec<Vec<char>>,
-    instructions: Vec<Instruction>,
+    state: Stacks,
+    /// Each instruction with the line it's on
+    instructions: Vec<(usize, Instruction)>,
+}
+
+/// A model of crane, which differ in how they move several crates at once
+trait Crane {
+    /// Take `count` crates off the top of `stack`, in the order they end up on the destination
+    fn pick_up(&self, stack: &mut Vec<char>, count: usize) -> Vec<char>;
+}
+
+/// Moves crates one at a time, so a pile ends up upside down
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn pick_up(&self, stack: &mut Vec<char>, count: usize) -> Vec<char> {
+        stack.drain(stack.len() - count..).rev().collect()
+    }
+}
+
+/// Moves the whole pile at once, keeping its order
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn pick_up(&self, stack: &mut Vec<char>, count: usize) -> Vec<char> {
+        stack.split_off(stack.len() - count)
+    }
+}
+
+fn crane(model: &str) -> Result<Box<dyn Crane>> {
+    match model {
+        "9000" => Ok(Box::new(CrateMover9000)),
+        "9001" => Ok(Box::new(CrateMover9001)),
+        _ => Err(anyhow!("Unknown crane model {model}")),
+    }
 }
 
-fn parse_instruction(instr: &str) -> Instruction {
+fn parse_instruction(instr: &str) -> Result<Instruction> {
     let split: Vec<&str> = instr.split(' ').collect();
-    if let [_move, count, _from, source, _to, dest] = split[..] {
-        let src: usize = source.parse().expect("Invalid source");
-        let dst: usize = dest.parse().expect("Invalid dest");
-        let cnt: usize = count.parse().expect("Invalid count");
-        Instruction {
-            source: src,
-            dest: dst,
-            count: cnt,
-        }
+    if let ["move", count, "from", source, "to", dest] = split[..] {
+        Ok(Instruction {
+            source: source.parse().context("Invalid source")?,
+            dest: dest.parse().context("Invalid dest")?,
+            count: count.parse().context("Invalid count")?,
+        })
     } else {
-        panic!("Invalid instruction")
+        Err(anyhow!("Expected `move N from A to B`"))
     }
 }
 
-fn parse_problem(description: &str) -> Problem {
-    let split: Vec<&str> = description.splitn(2, "\n\n").collect();
-    let initial_state = *split
-        .first()
-        .expect("Wrong formatting: No empty line separator");
-    let instructions = *split
-        .get(1)
-        .expect("Wrong formatting: No empty line separator");
-
-    Problem {
-        state: parse_stacks(initial_state),
+fn parse_problem(description: &str) -> Result<Problem> {
+    let (initial_state, instructions) = description
+        .split_once("\n\n")
+        .context("Wrong formatting: No empty line separator")?;
+    let first_instruction = initial_state.lines().count() + 2;
+    Ok(Problem {
+        state: parse_stacks(initial_state)?,
         instructions: instructions
-            .split('\n')
-            .filter(|s| !s.is_empty())
-            .map(parse_instruction)
-            .collect(),
-    }
-}
-
-fn parse_stacks(initial_state: &str) -> Vec<Vec<char>> {
-    let lines: Vec<&str> = initial_state.split('\n').collect();
-    let stack_count = lines
-        .last()
-        .expect("Wrong formatting: Empty initial_state")
-        .split(' ')
-        .filter(|s| !s.is_empty())
-        .count();
-    let mut out: Vec<Vec<char>> = Vec::with_capacity(stack_count);
-    for _ in 0..stack_count {
-        out.push(Vec::new());
-    }
-    for line in &lines[..lines.len() - 1] {
-        let line = *line;
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                let number = first_instruction + i;
+                let instr =
+                    parse_instruction(line).with_context(|| format!("line {number}: {line}"))?;
+                Ok((number, instr))
+            })
+            .collect::<Result<_>>()?,
+    })
+}
+
+fn parse_stacks(initial_state: &str) -> Result<Stacks> {
+    let lines: Vec<&str> = initial_state.lines().collect();
+    let (labels, crates) = lines
+        .split_last()
+        .context("Wrong formatting: Empty initial_state")?;
+    let stack_count = labels.split_whitespace().count();
+    let mut out: Stacks = vec![Vec::new(); stack_count];
+    // Going from the top down, so each stack comes out reversed
+    for (row, line) in crates.iter().enumerate() {
         for (i, ch) in line.chars().enumerate() {
             if ch.is_ascii_uppercase() {
-                let stack = out
-                    .get_mut(i / 4)
-                    .expect("Wrong formatting: Not that many stacks");
-                stack.push(ch.to_owned());
+                let stack = out.get_mut(i / 4).with_context(|| {
+                    format!(
+                        "line {}: column {}: Only {stack_count} stacks",
+                        row + 1,
+                        i + 1
+                    )
+                })?;
+                stack.push(ch);
             }
         }
     }
-    for i in 0..stack_count {
-        let v = out.get(i).unwrap();
-        out[i] = v.iter().cloned().rev().collect();
+    for stack in &mut out {
+        stack.reverse();
     }
-    out
+    Ok(out)
 }
 
-fn execute_instruction(problem: &mut Problem, instruction: usize) {
-    let instr = problem
-        .instructions
-        .get(instruction)
-        .expect("Wrong instructions index!");
-    for _i in 0..instr.count {
-        let source = problem
-            .state
-            .get_mut(instr.source - 1)
-            .expect("Wrong source");
-        let ch = source.pop().expect("Stack empty!");
-        let dest = problem.state.get_mut(instr.dest - 1).expect("Wrong dest");
-        dest.push(ch)
+/// Draw the stacks the way the puzzle input does, labels and all, minus trailing spaces
+fn render(stacks: &Stacks) -> String {
+    let height = stacks.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = String::new();
+    for level in (0..height).rev() {
+        let row: Vec<String> = stacks
+            .iter()
+            .map(|stack| match stack.get(level) {
+                Some(ch) => format!("[{ch}]"),
+                None => "   ".to_string(),
+            })
+            .collect();
+        out += row.join(" ").trim_end();
+        out.push('\n');
     }
+    let labels: Vec<String> = (1..=stacks.len()).map(|i| format!(" {i} ")).collect();
+    out += labels.join(" ").trim_end();
+    out.push('\n');
+    out
 }
 
-fn solve_part1(problem: &mut Problem) -> String {
-    for i in 0..problem.instructions.len() {
-        execute_instruction(problem, i)
+fn execute_instruction(state: &mut Stacks, instr: &Instruction, crane: &dyn Crane) -> Result<()> {
+    let stack = |number: usize| {
+        number
+            .checked_sub(1)
+            .filter(|&i| i < state.len())
+            .with_context(|| format!("{instr}: No stack {number}"))
+    };
+    let (source, dest) = (stack(instr.source)?, stack(instr.dest)?);
+    let available = state[source].len();
+    if instr.count > available {
+        return Err(anyhow!(
+            "{instr}: Stack {} only has {available} crates",
+            instr.source
+        ));
     }
-    problem
-        .state
-        .iter()
-        .map(|s| s.last().unwrap())
-        .cloned()
-        .collect()
+    let crates = crane.pick_up(&mut state[source], instr.count);
+    state[dest].extend(crates);
+    Ok(())
 }
 
-fn execute_instruction_part2(problem: &mut Problem, instruction: usize) {
-    let instr = problem
-        .instructions
-        .get(instruction)
-        .expect("Wrong instructions index!");
-    let source = problem
-        .state
-        .get_mut(instr.source - 1)
-        .expect("Wrong source");
-    let mut boxes: Vec<char> = Vec::with_capacity(instr.count);
-    for _ in 0..instr.count {
-        boxes.push(source.pop().expect("Empty stack"))
-    }
-    let dest = problem.state.get_mut(instr.dest - 1).expect("Wrong dest");
-    for _ in 0..instr.count {
-        dest.push(boxes.pop().expect("Empty move stack"));
+/// Carry out every instruction, calling `after_move` with the stacks as each move leaves them
+fn rearrange(
+    problem: &Problem,
+    crane: &dyn Crane,
+    mut after_move: impl FnMut(&Instruction, &Stacks),
+) -> Result<Stacks> {
+    let mut state = problem.state.clone();
+    for (line, instr) in &problem.instructions {
+        execute_instruction(&mut state, instr, crane).with_context(|| format!("line {line}"))?;
+        after_move(instr, &state);
     }
+    Ok(state)
 }
 
-fn solve_part2(problem: &mut Problem) -> String {
-    for i in 0..problem.instructions.len() {
-        execute_instruction_part2(problem, i)
-    }
-    problem
-        .state
+/// The crate on top of each stack, with a space for an empty one
+fn tops(state: &Stacks) -> String {
+    state
         .iter()
-        .map(|s| s.last().unwrap())
-        .cloned()
+        .map(|s| s.last().copied().unwrap_or(' '))
         .collect()
 }
 
-fn main() {
-    let lines: Vec<String> = io::stdin().lines().map(|l| l.unwrap()).collect();
-    let content = lines.join("\n");
-    let problem_text = content.as_ref();
+fn solve_part1(problem: &Problem) -> Result<String> {
+    Ok(tops(&rearrange(problem, &CrateMover9000, |_, _| ())?))
+}
+
+fn solve_part2(problem: &Problem) -> Result<String> {
+    Ok(tops(&rearrange(problem, &CrateMover9001, |_, _| ())?))
+}
 
-    let mut part1_problem = parse_problem(problem_text);
-    println!("Solve part 1: {}", solve_part1(&mut part1_problem));
+fn main() -> Result<()> {
+    let mut model = None;
+    let mut history = false;
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("model", value)) => model = Some(crane(value)?),
+            None if arg == "history" => history = true,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
+    let content = read_stdin()?;
+    let problem = parse_problem(&content)?;
 
-    let mut part2_problem = parse_problem(problem_text);
-    println!("Solve part 2: {}", solve_part2(&mut part2_problem));
+    if model.is_none() && !history {
+        println!("Solve part 1: {}", solve_part1(&problem)?);
+        println!("Solve part 2: {}", solve_part2(&problem)?);
+        return Ok(());
+    }
+    let model = model.unwrap_or_else(|| Box::new(CrateMover9000));
+    if history {
+        print!("{}", render(&problem.state));
+    }
+    let state = rearrange(&problem, model.as_ref(), |instr, state| {
+        if history {
+            print!("\n{instr}\n{}", render(state));
+        }
+    })?;
+    println!("{}", tops(&state));
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+";
+
     #[test]
     fn test_parse_instruction() {
-        let instr = parse_instruction("move 3 from 2 to 1");
+        let instr = parse_instruction("move 3 from 2 to 1").unwrap();
         assert_eq!(instr.count, 3);
         assert_eq!(instr.source, 2);
         assert_eq!(instr.dest, 1);
+        assert!(parse_instruction("move 3 from 2").is_err());
+        assert!(parse_instruction("move x from 2 to 1").is_err());
+    }
+
+    #[test]
+    fn test_solve() {
+        let problem = parse_problem(EXAMPLE).unwrap();
+        assert_eq!(solve_part1(&problem).unwrap(), "CMZ");
+        assert_eq!(solve_part2(&problem).unwrap(), "MCD");
+    }
+
+    #[test]
+    fn test_render() {
+        let problem = parse_problem(EXAMPLE).unwrap();
+        let drawing = EXAMPLE.split_once("\n\n").unwrap().0;
+        assert_eq!(render(&problem.state), format!("{drawing}\n"));
+        let mut drawings = Vec::new();
+        rearrange(&problem, &CrateMover9000, |_, state| {
+            drawings.push(render(state))
+        })
+        .unwrap();
+        assert_eq!(drawings.len(), 4);
+        assert_eq!(
+            drawings[1],
+            "        [Z]
+        [N]
+    [C] [D]
+    [M] [P]
+ 1   2   3
+"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |input: &str| format!("{:#}", parse_problem(input).err().unwrap());
+        assert_eq!(
+            error("[A]\n 1 \n\nmove 1 from 1 to 1\nmove 1 to 2\n"),
+            "line 5: move 1 to 2: Expected `move N from A to B`"
+        );
+        assert_eq!(
+            error("[A] [B]\n 1 \n\nmove 1 from 1 to 1\n"),
+            "line 1: column 6: Only 1 stacks"
+        );
+        assert!(parse_problem("[A]\n 1 \n").is_err());
+
+        let problem =
+            parse_problem("[A]\n 1   2 \n\nmove 1 from 1 to 2\nmove 2 from 2 to 1\n").unwrap();
+        let err = rearrange(&problem, &CrateMover9001, |_, _| ()).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 5: move 2 from 2 to 1: Stack 2 only has 1 crates"
+        );
+        let problem = parse_problem("[A]\n 1 \n\nmove 1 from 1 to 3\n").unwrap();
+        let err = solve_part1(&problem).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 4: move 1 from 1 to 3: No stack 3");
     }
 }
@@ -1,56 +1,299 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use aoc::io::read_stdin;
+use std::collections::BTreeMap;
 
-fn dir_traversal(inp: &str) -> Vec<i64> {
-    let mut stack = vec![0];
-    let mut out = vec![];
-
-    for line in inp.lines().filter(|line| !line.is_empty()) {
-        let mut parts = line.split_ascii_whitespace();
-
-        if line.starts_with("$ ls") || line.starts_with("dir") || line.starts_with("$ cd /") {
-            continue;
-        } else if line.starts_with("$ cd ..") {
-            out.push(stack.pop().unwrap());
-        } else if line.starts_with("$ cd ") {
-            stack.push(0);
-        } else {
-            let num = parts
-                .next()
-                .and_then(|num| num.parse::<i64>().ok())
-                .unwrap_or(0);
-            for total in &mut stack {
-                *total += num;
+type DirId = usize;
+const ROOT: DirId = 0;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Node {
+    File(i64),
+    Dir(DirId),
+}
+
+#[derive(Debug, Default)]
+struct Dir {
+    parent: Option<DirId>,
+    children: BTreeMap<String, Node>,
+    /// Whether `ls` has been run here, after which the contents are known exactly
+    listed: bool,
+}
+
+/// The directory tree pieced together from a terminal session
+#[derive(Debug)]
+struct FileSystem {
+    dirs: Vec<Dir>,
+}
+
+/// A directory listing in progress, checked against what was seen before once it's complete
+struct Listing {
+    dir: DirId,
+    seen: Vec<String>,
+}
+
+impl FileSystem {
+    fn from_log(log: &str) -> Result<FileSystem> {
+        let mut fs = FileSystem {
+            dirs: vec![Dir::default()],
+        };
+        let mut cwd = ROOT;
+        let mut listing: Option<Listing> = None;
+        for (i, line) in log.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let context = || format!("line {}: {line}", i + 1);
+            if let Some(command) = line.strip_prefix("$ ") {
+                if let Some(done) = listing.take() {
+                    fs.finish_listing(done).with_context(context)?;
+                }
+                match command.split_once(' ') {
+                    None if command == "ls" => {
+                        listing = Some(Listing {
+                            dir: cwd,
+                            seen: Vec::new(),
+                        })
+                    }
+                    Some(("cd", "/")) => cwd = ROOT,
+                    Some(("cd", "..")) => {
+                        cwd = fs.dirs[cwd]
+                            .parent
+                            .context("Can't go up from /")
+                            .with_context(context)?;
+                    }
+                    Some(("cd", name)) => cwd = fs.enter(cwd, name).with_context(context)?,
+                    _ => return Err(anyhow!("Unknown command")).with_context(context),
+                }
+            } else {
+                let current = listing
+                    .as_mut()
+                    .context("Output without a command")
+                    .with_context(context)?;
+                let (kind, name) = line
+                    .split_once(' ')
+                    .context("Expected a size or dir and a name")
+                    .with_context(context)?;
+                let size = match kind {
+                    "dir" => None,
+                    size => Some(size.parse().context("Bad size").with_context(context)?),
+                };
+                fs.add(current.dir, name, size).with_context(context)?;
+                current.seen.push(name.to_string());
+            }
+        }
+        if let Some(done) = listing {
+            fs.finish_listing(done).context("At the end of the log")?;
+        }
+        Ok(fs)
+    }
+
+    fn new_dir(&mut self, parent: DirId) -> DirId {
+        self.dirs.push(Dir {
+            parent: Some(parent),
+            ..Dir::default()
+        });
+        self.dirs.len() - 1
+    }
+
+    /// The directory `name` inside `dir`, which is assumed to exist if nothing says otherwise
+    fn enter(&mut self, dir: DirId, name: &str) -> Result<DirId> {
+        match self.dirs[dir].children.get(name) {
+            Some(&Node::Dir(child)) => Ok(child),
+            Some(Node::File(_)) => Err(anyhow!("{name} is a file")),
+            None if self.dirs[dir].listed => Err(anyhow!("No directory {name} in the listing")),
+            None => {
+                let child = self.new_dir(dir);
+                self.dirs[dir]
+                    .children
+                    .insert(name.to_string(), Node::Dir(child));
+                Ok(child)
             }
         }
     }
-    out.extend(stack);
-    out
+
+    /// Record an entry from a listing of `dir`, a file if it has a size
+    fn add(&mut self, dir: DirId, name: &str, size: Option<i64>) -> Result<()> {
+        match (self.dirs[dir].children.get(name), size) {
+            (None, _) if self.dirs[dir].listed => {
+                Err(anyhow!("{name} is missing from an earlier listing"))
+            }
+            (None, Some(size)) => {
+                self.dirs[dir]
+                    .children
+                    .insert(name.to_string(), Node::File(size));
+                Ok(())
+            }
+            (None, None) => {
+                let child = self.new_dir(dir);
+                self.dirs[dir]
+                    .children
+                    .insert(name.to_string(), Node::Dir(child));
+                Ok(())
+            }
+            (Some(Node::Dir(_)), None) => Ok(()),
+            (Some(&Node::File(old)), Some(size)) if old == size => Ok(()),
+            (Some(Node::File(old)), Some(size)) => {
+                Err(anyhow!("{name} was {old} bytes, now {size}"))
+            }
+            (Some(_), _) => Err(anyhow!("{name} changed between a file and a directory")),
+        }
+    }
+
+    fn finish_listing(&mut self, listing: Listing) -> Result<()> {
+        let dir = &mut self.dirs[listing.dir];
+        if dir.listed || dir.children.len() != listing.seen.len() {
+            let missing: Vec<_> = dir
+                .children
+                .keys()
+                .filter(|name| !listing.seen.contains(name))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                return Err(anyhow!("Listing is missing {}", missing.join(", ")));
+            }
+        }
+        dir.listed = true;
+        Ok(())
+    }
+
+    /// Total size of everything under `dir`
+    fn du(&self, dir: DirId) -> i64 {
+        self.dirs[dir]
+            .children
+            .values()
+            .map(|node| match node {
+                Node::File(size) => *size,
+                Node::Dir(child) => self.du(*child),
+            })
+            .sum()
+    }
+
+    /// The sizes of all directories, indexed like `dirs`
+    fn dir_sizes(&self) -> Vec<i64> {
+        let mut sizes = vec![0; self.dirs.len()];
+        // Children always come after their parents, so going backwards sees them first
+        for dir in (0..self.dirs.len()).rev() {
+            let files: i64 = self.dirs[dir]
+                .children
+                .values()
+                .map(|node| match node {
+                    Node::File(size) => *size,
+                    Node::Dir(child) => sizes[*child],
+                })
+                .sum();
+            sizes[dir] = files;
+        }
+        sizes
+    }
+
+    /// Find an absolute path like `/a/e`
+    fn resolve(&self, path: &str) -> Result<Node> {
+        let rest = path
+            .strip_prefix('/')
+            .with_context(|| format!("{path} isn't absolute"))?;
+        let mut node = Node::Dir(ROOT);
+        for name in rest.split('/').filter(|name| !name.is_empty()) {
+            node = match node {
+                Node::Dir(dir) => *self.dirs[dir]
+                    .children
+                    .get(name)
+                    .with_context(|| format!("No {name} in {path}"))?,
+                Node::File(_) => return Err(anyhow!("{path} goes through a file")),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Size of the file or directory at `path`
+    fn du_path(&self, path: &str) -> Result<i64> {
+        Ok(match self.resolve(path)? {
+            Node::File(size) => size,
+            Node::Dir(dir) => self.du(dir),
+        })
+    }
+
+    /// The puzzle's drawing of the tree, with directory sizes included
+    fn tree(&self) -> String {
+        let sizes = self.dir_sizes();
+        let mut out = format!("- / (dir, size={})\n", sizes[ROOT]);
+        self.tree_under(ROOT, 1, &sizes, &mut out);
+        out
+    }
+
+    fn tree_under(&self, dir: DirId, depth: usize, sizes: &[i64], out: &mut String) {
+        for (name, node) in &self.dirs[dir].children {
+            let indent = "  ".repeat(depth);
+            match *node {
+                Node::File(size) => *out += &format!("{indent}- {name} (file, size={size})\n"),
+                Node::Dir(child) => {
+                    *out += &format!("{indent}- {name} (dir, size={})\n", sizes[child]);
+                    self.tree_under(child, depth + 1, sizes, out);
+                }
+            }
+        }
+    }
+}
+
+struct Disk {
+    capacity: i64,
+    required: i64,
 }
 
-fn part_1(inp: &str) -> i64 {
-    let folder_sizes = dir_traversal(inp);
-    folder_sizes.iter().filter(|&size| *size <= 100000).sum()
+const PUZZLE_DISK: Disk = Disk {
+    capacity: 70000000,
+    required: 30000000,
+};
+
+fn part_1(fs: &FileSystem) -> i64 {
+    fs.dir_sizes()
+        .into_iter()
+        .filter(|&size| size <= 100000)
+        .sum()
 }
 
-fn part_2(inp: &str) -> i64 {
-    let cap = 70000000;
-    let req = 30000000;
-    let sizes = dir_traversal(inp);
-    let used = *sizes.iter().max().unwrap_or(&0);
-    let free = cap - used;
+/// The size of the smallest directory that frees up enough space once deleted, 0 if there
+/// already is enough
+fn part_2(fs: &FileSystem, disk: &Disk) -> Result<i64> {
+    let sizes = fs.dir_sizes();
+    let used = sizes[ROOT];
+    if used > disk.capacity {
+        return Err(anyhow!("{used} bytes used on a disk of {}", disk.capacity));
+    }
+    let free = disk.capacity - used;
+    if free >= disk.required {
+        return Ok(0);
+    }
     sizes
         .into_iter()
-        .filter(|&size| size + free >= req)
+        .filter(|&size| size + free >= disk.required)
         .min()
-        .unwrap_or(0)
+        .context("Even deleting everything isn't enough")
 }
 
 fn main() -> Result<()> {
+    let mut disk = PUZZLE_DISK;
+    let mut show_tree = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("capacity", value)) => disk.capacity = value.parse()?,
+            Some(("required", value)) => disk.required = value.parse()?,
+            Some(("du", path)) => paths.push(path.to_string()),
+            None if arg == "tree" => show_tree = true,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
     let inp = read_stdin()?;
-    let p1 = part_1(inp.as_str());
+    let fs = FileSystem::from_log(inp.as_str())?;
+    if show_tree {
+        print!("{}", fs.tree());
+    }
+    for path in paths {
+        println!("{}\t{path}", fs.du_path(&path)?);
+    }
+    let p1 = part_1(&fs);
     println!("{p1}");
-    let p2 = part_2(inp.as_str());
+    let p2 = part_2(&fs, &disk)?;
     println!("{p2}");
     Ok(())
 }
@@ -83,15 +326,93 @@ $ ls
 7214296 k
 ";
 
+    fn example() -> FileSystem {
+        FileSystem::from_log(EXAMPLE).unwrap()
+    }
+
     #[test]
     fn test_part_1() {
-        let ans = part_1(EXAMPLE);
+        let ans = part_1(&example());
         assert_eq!(ans, 95437);
     }
 
     #[test]
     fn test_part_2() {
-        let ans = part_2(EXAMPLE);
+        let ans = part_2(&example(), &PUZZLE_DISK).unwrap();
         assert_eq!(ans, 24933642);
+        let roomy = Disk {
+            capacity: 100000000,
+            required: 30000000,
+        };
+        assert_eq!(part_2(&example(), &roomy).unwrap(), 0);
+        let tiny = Disk {
+            capacity: 1000,
+            required: 10,
+        };
+        assert!(part_2(&example(), &tiny).is_err());
+    }
+
+    #[test]
+    fn test_du_and_tree() {
+        let fs = example();
+        assert_eq!(fs.du_path("/").unwrap(), 48381165);
+        assert_eq!(fs.du_path("/a").unwrap(), 94853);
+        assert_eq!(fs.du_path("/a/e/").unwrap(), 584);
+        assert_eq!(fs.du_path("/d/k").unwrap(), 7214296);
+        assert!(fs.du_path("/x").is_err());
+        assert!(fs.du_path("/d/k/x").is_err());
+        assert_eq!(
+            fs.tree(),
+            "\
+- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+    }
+
+    #[test]
+    fn test_revisits() {
+        // Listing / again after jumping back to it counts nothing twice
+        let log = format!("{EXAMPLE}$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n");
+        let fs = FileSystem::from_log(&log).unwrap();
+        assert_eq!(part_1(&fs), 95437);
+        assert_eq!(fs.du_path("/").unwrap(), 48381165);
+    }
+
+    #[test]
+    fn test_inconsistent_listings() {
+        let error = |log: &str| format!("{:#}", FileSystem::from_log(log).unwrap_err());
+        assert_eq!(
+            error("$ ls\n10 a\n$ ls\n20 a\n"),
+            "line 4: 20 a: a was 10 bytes, now 20"
+        );
+        assert_eq!(
+            error("$ ls\n10 a\n$ ls\ndir a\n"),
+            "line 4: dir a: a changed between a file and a directory"
+        );
+        assert_eq!(
+            error("$ ls\n10 a\n20 b\n$ ls\n10 a\n$ cd /\n"),
+            "line 6: $ cd /: Listing is missing b"
+        );
+        assert_eq!(
+            error("$ ls\n10 a\n$ ls\n10 a\n30 c\n"),
+            "line 5: 30 c: c is missing from an earlier listing"
+        );
+        assert_eq!(error("$ cd ..\n"), "line 1: $ cd ..: Can't go up from /");
+        assert_eq!(error("10 a\n"), "line 1: 10 a: Output without a command");
+        assert_eq!(error("$ ls\n10 a\n$ cd a\n"), "line 3: $ cd a: a is a file");
+        assert_eq!(error("$ rm -rf /\n"), "line 1: $ rm -rf /: Unknown command");
     }
 }
@@ -1,138 +1,256 @@
-use std::io::stdin;
+use anyhow::{anyhow, Context, Result};
+use aoc::io::read_stdin;
+use std::fmt::{self, Display, Formatter};
 
-struct Forest {
+/// Values laid out in rows, addressed by `(row, col)`
+#[derive(Debug, Clone)]
+struct Grid<T> {
     height: usize,
     width: usize,
-    forest: Vec<u8>,
+    cells: Vec<T>,
 }
 
-fn parse_forest(forest: &str) -> Forest {
-    let mut rows = 0;
-    let mut out = Vec::new();
+impl<T> Grid<T> {
+    fn get(&self, row: usize, col: usize) -> &T {
+        assert!(row < self.height && col < self.width, "Out of bounds");
+        &self.cells[row * self.width + col]
+    }
 
-    for line in forest.lines() {
-        rows += 1;
-        for height in line.as_bytes() {
-            out.push(*height - b'0')
+    fn get_mut(&mut self, row: usize, col: usize) -> &mut T {
+        assert!(row < self.height && col < self.width, "Out of bounds");
+        &mut self.cells[row * self.width + col]
+    }
+
+    /// Every cell with its coordinates, row by row
+    fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| ((i / self.width, i % self.width), cell))
+    }
+
+    fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            height: self.height,
+            width: self.width,
+            cells: self.cells.iter().map(f).collect(),
         }
     }
+}
 
-    Forest {
+type Forest = Grid<u8>;
+
+fn parse_forest(forest: &str) -> Result<Forest> {
+    let mut rows = 0;
+    let mut out = Vec::new();
+    for (i, line) in forest.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let heights = line
+            .bytes()
+            .map(|b| match b {
+                b'0'..=b'9' => Ok(b - b'0'),
+                _ => Err(anyhow!("Not a height: {:?}", b as char)),
+            })
+            .collect::<Result<Vec<u8>>>()
+            .with_context(|| format!("line {}", i + 1))?;
+        if rows > 0 && heights.len() * rows != out.len() {
+            return Err(anyhow!("line {}: Rows differ in length", i + 1));
+        }
+        out.extend(heights);
+        rows += 1;
+    }
+    if out.is_empty() {
+        return Err(anyhow!("No trees"));
+    }
+    Ok(Forest {
         height: rows,
         width: out.len() / rows,
-        forest: out,
+        cells: out,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+const SIDES: [Side; 4] = [Side::Top, Side::Bottom, Side::Left, Side::Right];
+
+impl Display for Side {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Side::Top => "top",
+            Side::Bottom => "bottom",
+            Side::Left => "left",
+            Side::Right => "right",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A set of sides
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+struct Sides(u8);
+
+impl Sides {
+    fn insert(&mut self, side: Side) {
+        self.0 |= 1 << side as u8;
+    }
+
+    fn contains(self, side: Side) -> bool {
+        self.0 & (1 << side as u8) != 0
+    }
+
+    fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    fn iter(self) -> impl Iterator<Item = Side> {
+        SIDES.into_iter().filter(move |&side| self.contains(side))
+    }
+}
+
+impl Display for Sides {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("hidden");
+        }
+        let names: Vec<String> = self.iter().map(|side| side.to_string()).collect();
+        f.write_str(&names.join(", "))
     }
 }
 
-fn index_forest(row: usize, col: usize, forest: &Forest) -> u8 {
-    let index = row * forest.width + col;
-    *forest.forest.get(index).expect("Out of bounds")
+/// What a tree sees of the forest
+#[derive(Debug, Clone, Copy, Default)]
+struct View {
+    /// The edges it can be seen from
+    visible_from: Sides,
+    /// How many trees it can see looking towards each side, in the order of `SIDES`
+    distances: [usize; 4],
 }
 
-fn decrement_height(row: usize, col: usize, forest: &mut Forest) {
-    let index = row * forest.width + col;
-    forest.forest[index] -= 1;
+impl View {
+    fn scenic_score(&self) -> usize {
+        self.distances.iter().product()
+    }
 }
 
-fn calculate_visibility_map(forest: &Forest) -> Forest {
-    let mut out_forest = Forest {
-        height: forest.height,
-        width: forest.width,
-        forest: Vec::with_capacity(forest.width * forest.height),
-    };
-    for _ in 0..(forest.height * forest.width) {
-        out_forest.forest.push(4_u8);
+/// The coordinates of each line of trees as seen from `side`, nearest the edge first
+fn lines_from(forest: &Forest, side: Side) -> Vec<Vec<(usize, usize)>> {
+    let (rows, cols) = (0..forest.height, 0..forest.width);
+    match side {
+        Side::Top => cols
+            .map(|c| rows.clone().map(|r| (r, c)).collect())
+            .collect(),
+        Side::Bottom => cols
+            .map(|c| rows.clone().rev().map(|r| (r, c)).collect())
+            .collect(),
+        Side::Left => rows
+            .map(|r| cols.clone().map(|c| (r, c)).collect())
+            .collect(),
+        Side::Right => rows
+            .map(|r| cols.clone().rev().map(|c| (r, c)).collect())
+            .collect(),
     }
-    for row in 0..forest.height {
-        for col in 0..forest.width {
-            let tree_height = index_forest(row, col, forest);
+}
 
-            for above in 0..row {
-                if index_forest(above, col, forest) >= tree_height {
-                    decrement_height(row, col, &mut out_forest);
-                    break;
+/// Work out every tree's view with one pass in from each edge.
+///
+/// Walking a line away from the edge, a stack holds the trees that still block the view back
+/// towards it, in decreasing height. Anything shorter than the current tree is hidden behind
+/// it from then on, so it's popped for good; whatever remains on top is the tree blocking the
+/// view, and an empty stack means the tree can see, and be seen from, the edge.
+fn survey(forest: &Forest) -> Grid<View> {
+    let mut views = forest.map(|_| View::default());
+    let mut stack: Vec<usize> = Vec::new();
+    for (s, side) in SIDES.into_iter().enumerate() {
+        for line in lines_from(forest, side) {
+            stack.clear();
+            for (i, &(row, col)) in line.iter().enumerate() {
+                let height = *forest.get(row, col);
+                while let Some(&top) = stack.last() {
+                    let (r, c) = line[top];
+                    if *forest.get(r, c) >= height {
+                        break;
+                    }
+                    stack.pop();
                 }
-            }
-            for below in (row + 1)..forest.height {
-                if index_forest(below, col, forest) >= tree_height {
-                    decrement_height(row, col, &mut out_forest);
-                    break;
-                }
-            }
-            for left in 0..col {
-                if index_forest(row, left, forest) >= tree_height {
-                    decrement_height(row, col, &mut out_forest);
-                    break;
-                }
-            }
-            for right in (col + 1)..forest.width {
-                if index_forest(row, right, forest) >= tree_height {
-                    decrement_height(row, col, &mut out_forest);
-                    break;
+                let view = views.get_mut(row, col);
+                match stack.last() {
+                    Some(&blocker) => view.distances[s] = i - blocker,
+                    None => {
+                        view.distances[s] = i;
+                        view.visible_from.insert(side);
+                    }
                 }
+                stack.push(i);
             }
         }
     }
-    out_forest
+    views
 }
 
-fn calculate_scenic_score_map(forest: &Forest) -> Vec<i32> {
-    let mut out = Vec::new();
+fn calculate_visibility_map(forest: &Forest) -> Grid<Sides> {
+    survey(forest).map(|view| view.visible_from)
+}
 
-    for row in 1..forest.height - 1 {
-        for col in 1..forest.width - 1 {
-            let tree_height = index_forest(row, col, forest);
-            let mut seen_left = 0;
-            let mut seen_right = 0;
-            for i in (0..col).rev() {
-                seen_left += 1;
-                if tree_height <= index_forest(row, i, forest) {
-                    break;
-                }
-            }
-            for i in col + 1..forest.width {
-                seen_right += 1;
-                if tree_height <= index_forest(row, i, forest) {
-                    break;
-                }
-            }
-            let mut seen_above = 0;
-            let mut seen_below = 0;
-            for i in (0..row).rev() {
-                seen_above += 1;
-                if tree_height <= index_forest(i, col, forest) {
-                    break;
-                }
-            }
-            for i in (row + 1)..forest.width {
-                seen_below += 1;
-                if tree_height <= index_forest(i, col, forest) {
-                    break;
-                }
-            }
+fn calculate_scenic_score_map(forest: &Forest) -> Grid<usize> {
+    survey(forest).map(View::scenic_score)
+}
+
+fn count_visible(visibility: &Grid<Sides>) -> usize {
+    visibility
+        .cells
+        .iter()
+        .filter(|sides| !sides.is_empty())
+        .count()
+}
 
-            let scenic = seen_left * seen_right * seen_above * seen_below;
-            out.push(scenic);
+/// The `k` trees with the best scenic scores, best first and in reading order on ties
+fn top_scenic(scores: &Grid<usize>, k: usize) -> Vec<((usize, usize), usize)> {
+    let mut trees: Vec<_> = scores.iter().map(|(pos, &score)| (pos, score)).collect();
+    trees.sort_by(|(a_pos, a), (b_pos, b)| b.cmp(a).then(a_pos.cmp(b_pos)));
+    trees.truncate(k);
+    trees
+}
+
+fn main() -> Result<()> {
+    let mut top = None;
+    let mut trees = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("top", value)) => top = Some(value.parse()?),
+            Some(("sides", value)) => {
+                let (row, col) = value.split_once(',').context("Expected sides=row,col")?;
+                trees.push((row.parse()?, col.parse()?));
+            }
+            _ => return Err(anyhow!("Unknown option {arg}")),
         }
     }
-    out
-}
-
-fn main() {
-    let input: Vec<String> = stdin()
-        .lines()
-        .map(|line| line.expect("IO error"))
-        .filter(|line| !line.is_empty())
-        .collect();
-    let text = input.join("\n");
-    let forest = parse_forest(text.as_str());
-    print!("Made forest");
-    let height_map = calculate_visibility_map(&forest);
-    let visible = height_map.forest.iter().filter(|tree| *tree > &0).count();
-    println!("Part 1: {}", visible);
+    let text = read_stdin()?;
+    let forest = parse_forest(text.as_str())?;
+    let visibility = calculate_visibility_map(&forest);
+    println!("Part 1: {}", count_visible(&visibility));
     let scenic_map = calculate_scenic_score_map(&forest);
-    let scenic = scenic_map.iter().max();
+    let scenic = scenic_map.cells.iter().max();
     println!("Part 2: {}", scenic.unwrap_or(&0));
+    if let Some(k) = top {
+        for ((row, col), score) in top_scenic(&scenic_map, k) {
+            println!("{row},{col}\t{score}");
+        }
+    }
+    for (row, col) in trees {
+        if row >= forest.height || col >= forest.width {
+            return Err(anyhow!("No tree at {row},{col}"));
+        }
+        println!("{row},{col}\t{}", visibility.get(row, col));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -147,27 +265,73 @@ mod tests {
 
     #[test]
     fn test_scenic_visibility_map() {
-        let forest = parse_forest(EXAMPLE);
+        let forest = parse_forest(EXAMPLE).unwrap();
         let height_map = calculate_scenic_score_map(&forest);
-        let scenic = *height_map.iter().max().unwrap_or(&0);
+        let scenic = *height_map.cells.iter().max().unwrap_or(&0);
         assert_eq!(scenic, 8);
+        assert_eq!(*height_map.get(1, 2), 4);
+        assert_eq!(*height_map.get(0, 3), 0);
+        assert_eq!(
+            top_scenic(&height_map, 3),
+            vec![((3, 2), 8), ((2, 1), 6), ((1, 2), 4)]
+        );
     }
 
     #[test]
     fn test_calc_visibility_map() {
-        let forest = parse_forest(EXAMPLE);
+        let forest = parse_forest(EXAMPLE).unwrap();
         let height_map = calculate_visibility_map(&forest);
-        let visible = height_map.forest.iter().filter(|tree| *tree > &0).count();
-        assert_eq!(visible, 21);
+        assert_eq!(count_visible(&height_map), 21);
+        assert_eq!(height_map.get(1, 1).to_string(), "top, left");
+        assert_eq!(height_map.get(1, 2).to_string(), "top, right");
+        assert_eq!(height_map.get(2, 1).to_string(), "right");
+        assert_eq!(height_map.get(3, 3).to_string(), "hidden");
+        assert_eq!(
+            height_map.get(0, 0).iter().collect::<Vec<_>>(),
+            vec![Side::Top, Side::Left]
+        );
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        // A forest with plenty of equal heights, checked against looking along each line
+        let text: String = (0..12)
+            .map(|r| {
+                (0..9)
+                    .map(|c| ((r * 7 + c * c * 3) % 5).to_string())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect();
+        let forest = parse_forest(&text).unwrap();
+        let views = survey(&forest);
+        for ((row, col), view) in views.iter() {
+            let height = *forest.get(row, col);
+            let lines: [Vec<(usize, usize)>; 4] = [
+                (0..row).rev().map(|r| (r, col)).collect(),
+                (row + 1..forest.height).map(|r| (r, col)).collect(),
+                (0..col).rev().map(|c| (row, c)).collect(),
+                (col + 1..forest.width).map(|c| (row, c)).collect(),
+            ];
+            for (s, line) in lines.iter().enumerate() {
+                let blocker = line.iter().position(|&(r, c)| *forest.get(r, c) >= height);
+                assert_eq!(view.visible_from.contains(SIDES[s]), blocker.is_none());
+                assert_eq!(view.distances[s], blocker.map_or(line.len(), |i| i + 1));
+            }
+        }
     }
 
     #[test]
     fn test_parse_example() {
-        let forest = parse_forest(EXAMPLE);
+        let forest = parse_forest(EXAMPLE).unwrap();
         assert_eq!(forest.height, 5);
         assert_eq!(forest.width, 5);
-        assert_eq!(index_forest(0, 0, &forest), 3);
-        assert_eq!(index_forest(1, 1, &forest), 5);
-        assert_eq!(index_forest(4, 2, &forest), 3);
+        assert_eq!(*forest.get(0, 0), 3);
+        assert_eq!(*forest.get(1, 1), 5);
+        assert_eq!(*forest.get(4, 2), 3);
+        let err = parse_forest("123\n12\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: Rows differ in length");
+        let err = parse_forest("123\n1x3\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: Not a height: 'x'");
     }
 }
@@ -1,5 +1,10 @@
+use anyhow::{anyhow, Context, Result};
+use aoc::io::read_stdin;
+use aoc::point2d::{Point2d, Rect};
 use std::collections::HashSet;
-use std::io::stdin;
+use std::fmt::{self, Display, Formatter};
+
+type Pos = Point2d<i32>;
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum Move {
@@ -8,196 +13,185 @@ pub enum Move {
     Left(i32),
     Right(i32),
 }
-fn move_count(instruction: &Move) -> i32 {
-    match instruction {
-        Move::Up(count) => *count,
-        Move::Left(count) => *count,
-        Move::Right(count) => *count,
-        Move::Down(count) => *count,
+
+impl Move {
+    fn count(&self) -> i32 {
+        match self {
+            Move::Up(count) | Move::Down(count) | Move::Left(count) | Move::Right(count) => *count,
+        }
+    }
+
+    /// A single step in the direction of the move, with up as north
+    fn step(&self) -> Pos {
+        let origin = Pos::origin();
+        match self {
+            Move::Up(_) => origin.north(),
+            Move::Down(_) => origin.south(),
+            Move::Left(_) => origin.west(),
+            Move::Right(_) => origin.east(),
+        }
     }
 }
 
-fn parse_moves(input: &str) -> Vec<Move> {
-    fn to_move(line: &str) -> Move {
-        let mut parts = line.split(' ');
-        let direction = parts.next().expect("Missing direction");
-        let count: i32 = parts
-            .next()
-            .expect("Missing step count")
-            .parse()
-            .expect("Bad number");
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let direction = match self {
+            Move::Up(_) => 'U',
+            Move::Down(_) => 'D',
+            Move::Left(_) => 'L',
+            Move::Right(_) => 'R',
+        };
+        write!(f, "{direction} {}", self.count())
+    }
+}
+
+fn parse_moves(input: &str) -> Result<Vec<Move>> {
+    fn to_move(line: &str) -> Result<Move> {
+        let (direction, count) = line
+            .split_once(' ')
+            .context("Expected a direction and a step count")?;
+        let count: i32 = count.parse().context("Bad number")?;
         match direction {
-            "U" => Move::Up(count),
-            "L" => Move::Left(count),
-            "D" => Move::Down(count),
-            "R" => Move::Right(count),
-            _ => panic!("Unknown direction: {}", direction),
+            "U" => Ok(Move::Up(count)),
+            "L" => Ok(Move::Left(count)),
+            "D" => Ok(Move::Down(count)),
+            "R" => Ok(Move::Right(count)),
+            _ => Err(anyhow!("Unknown direction: {direction}")),
         }
     }
 
     input
         .lines()
-        .filter(|line| !line.is_empty())
-        .map(to_move)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| to_move(line).with_context(|| format!("line {}: {line}", i + 1)))
         .collect()
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-struct Pos {
-    x: i32,
-    y: i32,
+/// Where a knot ends up after the one in front of it moved to `head`
+fn follow(head: Pos, tail: Pos) -> Pos {
+    let diff = head - tail;
+    if diff.x.abs() <= 1 && diff.y.abs() <= 1 {
+        tail
+    } else {
+        tail + diff.signum()
+    }
 }
-#[derive(Debug, Clone, Copy)]
-pub struct State {
-    head: Pos,
-    tail: Pos,
+
+struct Rope {
+    /// Head first
+    knots: Vec<Pos>,
+    /// Everywhere each knot has been
+    visited: Vec<HashSet<Pos>>,
 }
 
-fn move_tail(head: &Pos, tail: &Pos) -> Pos {
-    if head.x == tail.x {
-        let y_diff = head.y - tail.y;
-        if y_diff >= 2 {
-            Pos {
-                x: tail.x,
-                y: tail.y + 1,
-            }
-        } else if y_diff <= -2 {
-            Pos {
-                x: tail.x,
-                y: tail.y - 1,
-            }
-        } else {
-            *tail
-        }
-    } else if head.y == tail.y {
-        let x_diff = head.x - tail.x;
-        if x_diff >= 2 {
-            Pos {
-                x: tail.x + 1,
-                y: tail.y,
-            }
-        } else if x_diff <= -2 {
-            Pos {
-                x: tail.x - 1,
-                y: tail.y,
-            }
-        } else {
-            *tail
-        }
-    } else {
-        let x_diff = head.x - tail.x;
-        let y_diff = head.y - tail.y;
-        let manhattan_dist = x_diff.abs() + y_diff.abs();
-        if manhattan_dist > 2 {
-            let x_move = if x_diff < 0 { -1 } else { 1 };
-            let y_move = if y_diff < 0 { -1 } else { 1 };
-            Pos {
-                x: tail.x + x_move,
-                y: tail.y + y_move,
-            }
-        } else {
-            *tail
+impl Rope {
+    /// A rope with all its knots at the origin. Panics if there are none.
+    fn new(knots: usize) -> Self {
+        assert!(knots > 0, "A rope needs at least one knot");
+        Self {
+            knots: vec![Pos::origin(); knots],
+            visited: vec![HashSet::from([Pos::origin()]); knots],
         }
     }
-}
-
-fn execute_move(state: State, instruction: &Move) -> (State, HashSet<Pos>) {
-    let mut head = state.head;
-    let mut tail = state.tail;
-    let mut tail_positions = HashSet::new();
-    tail_positions.insert(tail);
 
-    match instruction {
-        Move::Up(count) => {
-            for _ in 0..*count {
-                head.y += 1;
-                tail = move_tail(&head, &tail);
-                tail_positions.insert(tail);
+    fn apply(&mut self, instr: &Move) {
+        let step = instr.step();
+        for _ in 0..instr.count() {
+            self.knots[0] = self.knots[0] + step;
+            for i in 1..self.knots.len() {
+                self.knots[i] = follow(self.knots[i - 1], self.knots[i]);
             }
-        }
-        Move::Down(count) => {
-            for _ in 0..*count {
-                head.y -= 1;
-                tail = move_tail(&head, &tail);
-                tail_positions.insert(tail);
+            for (knot, visited) in self.knots.iter().zip(&mut self.visited) {
+                visited.insert(*knot);
             }
         }
-        Move::Right(count) => {
-            for _ in 0..*count {
-                head.x += 1;
-                tail = move_tail(&head, &tail);
-                tail_positions.insert(tail);
-            }
+    }
+
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            _ if self.knots.len() == 2 => 'T',
+            _ => char::from_digit(knot as u32 % 36, 36).unwrap_or('?'),
         }
-        Move::Left(count) => {
-            for _ in 0..*count {
-                head.x -= 1;
-                tail = move_tail(&head, &tail);
-                tail_positions.insert(tail);
+    }
+
+    /// Draw the area like the puzzle does: the knots, with those nearer the head on top, then
+    /// `s` for the start and `#` where the tail has been
+    fn render(&self, area: Rect<i32>) -> String {
+        let mut out = String::new();
+        for y in area.northwest.y..=area.southeast.y {
+            for x in area.northwest.x..=area.southeast.x {
+                let pos = Pos::new(x, y);
+                out.push(match self.knots.iter().position(|&knot| knot == pos) {
+                    Some(knot) => self.label(knot),
+                    None if pos == Pos::origin() => 's',
+                    None if self.visited[self.knots.len() - 1].contains(&pos) => '#',
+                    None => '.',
+                });
             }
+            out.push('\n');
         }
+        out
     }
-
-    (State { head, tail }, tail_positions)
 }
 
-fn part2(inp: &str) -> HashSet<Pos> {
-    let moves = parse_moves(inp);
-    let mut states: Vec<Pos> = Vec::new();
-    let mut last_tail_places = HashSet::new();
-    for _ in 0..10 {
-        states.push(Pos { x: 0, y: 0 });
-    }
+/// Everywhere each knot of a rope of `knots` knots goes, head first
+fn simulate(moves: &[Move], knots: usize) -> Vec<HashSet<Pos>> {
+    let mut rope = Rope::new(knots);
     for instr in moves {
-        for _ in 0..move_count(&instr) {
-            let mut head = states[0];
-            match instr {
-                Move::Up(_) => {
-                    head.y += 1;
-                }
-                Move::Down(_) => {
-                    head.y -= 1;
-                }
-                Move::Left(_) => {
-                    head.x -= 1;
-                }
-                Move::Right(_) => {
-                    head.x += 1;
-                }
-            }
-            states[0] = head;
+        rope.apply(instr);
+    }
+    rope.visited
+}
 
-            for i in 1..10 {
-                let head = states[i - 1];
-                states[i] = move_tail(&head, &states[i]);
-            }
+fn tail_visits(moves: &[Move], knots: usize) -> usize {
+    simulate(moves, knots).last().map_or(0, HashSet::len)
+}
 
-            last_tail_places.insert(states[9]);
-        }
+/// The rope after each move, drawn over an area big enough for the whole walkthrough
+fn walkthrough(moves: &[Move], knots: usize) -> Vec<String> {
+    let area = Rect::bound(simulate(moves, knots).into_iter().flatten())
+        .expect("The origin is always visited");
+    let mut rope = Rope::new(knots);
+    let mut frames = vec![rope.render(area)];
+    for instr in moves {
+        rope.apply(instr);
+        frames.push(rope.render(area));
     }
-    last_tail_places
+    frames
 }
 
-fn main() {
-    let input: Vec<String> = stdin()
-        .lines()
-        .map(|line| line.expect("IO Error"))
-        .collect();
-    let as_str = input.join("\n");
-    let moves = parse_moves(as_str.as_str());
-    let mut places_seen = HashSet::new();
-    let mut state = State {
-        head: Pos { x: 0, y: 0 },
-        tail: Pos { x: 0, y: 0 },
-    };
-    for instr in moves {
-        let (next_state, new_places) = execute_move(state, &instr);
-        state = next_state;
-        places_seen = places_seen.union(&new_places).copied().collect();
+fn main() -> Result<()> {
+    let mut knots = None;
+    let mut render = false;
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("knots", value)) => match value.parse()? {
+                0 => return Err(anyhow!("A rope needs at least one knot")),
+                count => knots = Some(count),
+            },
+            None if arg == "render" => render = true,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
+    let input = read_stdin()?;
+    let moves = parse_moves(&input)?;
+    if render {
+        let frames = walkthrough(&moves, knots.unwrap_or(2));
+        println!("== Initial State ==\n\n{}", frames[0]);
+        for (instr, frame) in moves.iter().zip(&frames[1..]) {
+            println!("== {instr} ==\n\n{frame}");
+        }
     }
-    println!("{}", places_seen.len());
-    let p2 = part2(as_str.as_str());
-    println!("{}", p2.len());
+    match knots {
+        Some(knots) => println!("{}", tail_visits(&moves, knots)),
+        None => {
+            println!("{}", tail_visits(&moves, 2));
+            println!("{}", tail_visits(&moves, 10));
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -212,6 +206,16 @@ R 4
 D 1
 L 5
 R 2
+";
+
+    const LARGER_EXAMPLE: &str = "R 5
+U 8
+L 8
+D 3
+R 17
+D 10
+L 25
+U 20
 ";
 
     #[test]
@@ -226,55 +230,49 @@ R 2
             Move::Left(5),
             Move::Right(2),
         ];
-        let parsed = parse_moves(EXAMPLE);
+        let parsed = parse_moves(EXAMPLE).unwrap();
         assert_eq!(parsed, expected);
+        assert_eq!(parsed[2].to_string(), "L 3");
+        let err = parse_moves("R 4\nX 2\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: X 2: Unknown direction: X");
+        assert!(parse_moves("R\n").is_err());
     }
 
     #[test]
-    fn test_move_tail_in_line() {
-        assert_eq!(
-            move_tail(&Pos { x: 0, y: 2 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 0, y: 1 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: 0, y: 1 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 0, y: 0 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: 0, y: -2 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 0, y: -1 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: 2, y: 0 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 1, y: 0 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: 1, y: 1 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 0, y: 0 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: 2, y: 1 }, &Pos { x: 0, y: 0 }),
-            Pos { x: 1, y: 1 }
-        );
-        assert_eq!(
-            move_tail(&Pos { x: -2, y: 1 }, &Pos { x: 0, y: 0 }),
-            Pos { x: -1, y: 1 }
-        );
+    fn test_follow() {
+        let origin = Pos::origin();
+        assert_eq!(follow(Pos::new(0, 2), origin), Pos::new(0, 1));
+        assert_eq!(follow(Pos::new(0, 1), origin), origin);
+        assert_eq!(follow(Pos::new(0, -2), origin), Pos::new(0, -1));
+        assert_eq!(follow(Pos::new(2, 0), origin), Pos::new(1, 0));
+        assert_eq!(follow(Pos::new(1, 1), origin), origin);
+        assert_eq!(follow(Pos::new(2, 1), origin), Pos::new(1, 1));
+        assert_eq!(follow(Pos::new(-2, 1), origin), Pos::new(-1, 1));
+        assert_eq!(follow(Pos::new(2, 2), origin), Pos::new(1, 1));
     }
 
     #[test]
     fn test_moves_example() {
-        let moves = parse_moves(EXAMPLE);
-        let mut places_seen = HashSet::new();
-        let mut state = State {
-            head: Pos { x: 0, y: 0 },
-            tail: Pos { x: 0, y: 0 },
-        };
-        for instr in moves {
-            let (next_state, new_places) = execute_move(state, &instr);
-            state = next_state;
-            places_seen = places_seen.union(&new_places).copied().collect();
-        }
-        assert_eq!(places_seen.len(), 13);
+        let moves = parse_moves(EXAMPLE).unwrap();
+        assert_eq!(tail_visits(&moves, 2), 13);
+        assert_eq!(tail_visits(&moves, 10), 1);
+        let visited = simulate(&moves, 2);
+        assert_eq!(visited.len(), 2);
+        assert!(visited[0].contains(&Pos::new(4, -4)));
+        assert!(!visited[1].contains(&Pos::new(4, -4)));
+        let larger = parse_moves(LARGER_EXAMPLE).unwrap();
+        assert_eq!(tail_visits(&larger, 10), 36);
+    }
+
+    #[test]
+    fn test_walkthrough() {
+        let moves = parse_moves(EXAMPLE).unwrap();
+        let frames = walkthrough(&moves, 2);
+        assert_eq!(frames.len(), 9);
+        assert_eq!(frames[0], "......\n......\n......\n......\nH.....\n");
+        assert_eq!(frames[2], "....H.\n....T.\n....#.\n....#.\ns###..\n");
+        assert_eq!(frames[8], "..##..\n...##.\n.TH##.\n....#.\ns###..\n");
+        let frames = walkthrough(&moves, 10);
+        assert_eq!(frames[2], "....H.\n....1.\n..432.\n.5....\n6.....\n");
     }
 }
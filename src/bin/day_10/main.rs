@@ -1,96 +1,495 @@
-use std::io::stdin;
+use anyhow::{anyhow, Context, Result};
+use aoc::io::read_stdin;
+use aoc::ocr::parse_letters;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
-#[derive(Debug, PartialEq)]
+/// Registers are named by a single letter, `addx` adds to `x`, `addy` to `y` and so on
+const REGISTER_NAMES: [char; 4] = ['x', 'y', 'z', 'w'];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Register(usize);
+
+impl Register {
+    const X: Register = Register(0);
+
+    fn from_name(name: char) -> Option<Register> {
+        REGISTER_NAMES.iter().position(|&r| r == name).map(Register)
+    }
+
+    fn name(&self) -> char {
+        REGISTER_NAMES[self.0]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Op {
+    Noop,
+    Add,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Instruction {
-    Add(i32, usize),
-    Noop(),
+    Noop,
+    Add(Register, i32),
+}
+
+impl Instruction {
+    fn op(&self) -> Op {
+        match self {
+            Instruction::Noop => Op::Noop,
+            Instruction::Add(_, _) => Op::Add,
+        }
+    }
+}
+
+/// How many cycles each operation takes to complete
+#[derive(Debug, Clone)]
+pub struct InstructionTable {
+    cycles: HashMap<Op, usize>,
+}
+
+impl Default for InstructionTable {
+    fn default() -> Self {
+        Self {
+            cycles: [(Op::Noop, 1), (Op::Add, 2)].into_iter().collect(),
+        }
+    }
+}
+
+impl InstructionTable {
+    fn with_cost(mut self, op: Op, cycles: usize) -> Self {
+        self.cycles.insert(op, cycles);
+        self
+    }
+
+    /// Every instruction takes at least one cycle
+    fn cycles(&self, op: Op) -> usize {
+        self.cycles.get(&op).copied().unwrap_or(1).max(1)
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Add(register, count) => write!(f, "add{} {count}", register.name()),
+        }
+    }
 }
 
-fn parse_instruction(instr: &str) -> Instruction {
+fn parse_instruction(instr: &str) -> Result<Instruction> {
     let mut parts = instr.split(' ');
-    let first = parts.next().expect("Empty instruction");
-    match first {
-        "noop" => Instruction::Noop(),
-        "addx" => Instruction::Add(
-            parts
+    let first = parts.next().unwrap_or_default();
+    let instruction = match first {
+        "noop" => Instruction::Noop,
+        _ if first.len() == 4 && first.starts_with("add") => {
+            let register = first
+                .chars()
+                .last()
+                .and_then(Register::from_name)
+                .with_context(|| format!("unknown register in `{first}`"))?;
+            let operand = parts
                 .next()
-                .and_then(|arg| arg.parse().ok())
-                .expect("Missing operand"),
-            2,
-        ),
-        _ => panic!("Wrong instruction: {}", first),
+                .with_context(|| format!("missing operand for `{first}`"))?;
+            let count = operand
+                .parse()
+                .with_context(|| format!("bad operand `{operand}` for `{first}`"))?;
+            Instruction::Add(register, count)
+        }
+        _ => return Err(anyhow!("unknown instruction `{first}`")),
+    };
+    match parts.next() {
+        Some(extra) => Err(anyhow!("unexpected `{extra}` after `{first}`")),
+        None => Ok(instruction),
     }
 }
 
-fn parse_instructions<'a, I: Iterator<Item = &'a str> + 'a>(it: I) -> Program<'a> {
-    let out = it.filter(|l: &&str| !l.is_empty()).map(parse_instruction);
-    Program {
-        source: Box::new(out),
-        register: 1,
-        op: None,
-        started: false,
+/// Parse a program with one instruction per line, blank lines are skipped
+fn assemble(source: &str) -> Result<Vec<Instruction>> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(n, line)| parse_instruction(line).map_err(|e| anyhow!("line {}: {e}", n + 1)))
+        .collect()
+}
+
+/// Print a program in the format read by `assemble`
+fn disassemble(program: &[Instruction]) -> String {
+    program
+        .iter()
+        .map(|instruction| format!("{instruction}\n"))
+        .collect()
+}
+
+fn parse_instructions<'a, I: Iterator<Item = &'a str> + 'a>(it: I) -> Result<Cpu> {
+    let source = it.collect::<Vec<_>>().join("\n");
+    Ok(Cpu::new(assemble(&source)?, InstructionTable::default()))
+}
+
+/// The state of the registers during a cycle
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Tick {
+    cycle: usize,
+    registers: [i32; REGISTER_NAMES.len()],
+}
+
+impl Tick {
+    fn register(&self, register: Register) -> i32 {
+        self.registers[register.0]
+    }
+
+    fn x(&self) -> i32 {
+        self.register(Register::X)
     }
 }
 
-struct Program<'a> {
-    source: Box<dyn Iterator<Item = Instruction> + 'a>,
-    register: i32,
-    op: Option<Instruction>,
-    started: bool,
+impl Display for Tick {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cycle {:>4}", self.cycle)?;
+        for (name, value) in REGISTER_NAMES.iter().zip(self.registers) {
+            write!(f, " {name}={value}")?;
+        }
+        Ok(())
+    }
 }
 
-impl Iterator for Program<'_> {
-    type Item = i32;
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    /// Stopped before running the cycle
+    Breakpoint(usize),
+    /// A watched register changed value at the end of the cycle
+    Watchpoint {
+        cycle: usize,
+        register: Register,
+        old: i32,
+        new: i32,
+    },
+    Halted,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.started && self.op.is_none() {
-            None
-        } else {
-            self.started = true;
-            match self.op {
-                None | Some(Instruction::Noop()) => {
-                    self.op = self.source.next();
-                    Some(self.register)
-                }
-                Some(Instruction::Add(count, cycles_remaining)) => {
-                    if cycles_remaining == 1 {
-                        self.op = self.source.next();
-                        self.register += count;
-                    } else {
-                        self.op = Some(Instruction::Add(count, cycles_remaining - 1));
-                    }
-                    Some(self.register)
-                }
+pub struct Cpu {
+    program: Vec<Instruction>,
+    table: InstructionTable,
+    pc: usize,
+    /// Cycles left before `program[pc]` completes, 0 if it hasn't started
+    remaining: usize,
+    /// The number of completed cycles
+    cycle: usize,
+    registers: [i32; REGISTER_NAMES.len()],
+    breakpoints: HashSet<usize>,
+    watchpoints: HashSet<Register>,
+    resumed_from: Option<usize>,
+    trace: bool,
+}
+
+impl Cpu {
+    fn new(program: Vec<Instruction>, table: InstructionTable) -> Self {
+        Self {
+            program,
+            table,
+            pc: 0,
+            remaining: 0,
+            cycle: 0,
+            registers: [1; REGISTER_NAMES.len()],
+            breakpoints: HashSet::new(),
+            watchpoints: HashSet::new(),
+            resumed_from: None,
+            trace: false,
+        }
+    }
+
+    /// Run a single cycle, returning the register state during that cycle
+    fn step(&mut self) -> Option<Tick> {
+        let instruction = *self.program.get(self.pc)?;
+        if self.remaining == 0 {
+            self.remaining = self.table.cycles(instruction.op());
+        }
+        self.cycle += 1;
+        let tick = Tick {
+            cycle: self.cycle,
+            registers: self.registers,
+        };
+        if self.trace {
+            println!("{tick}");
+        }
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            match instruction {
+                Instruction::Noop => {}
+                Instruction::Add(register, count) => self.registers[register.0] += count,
+            }
+            self.pc += 1;
+        }
+        Some(tick)
+    }
+
+    /// Run until the program ends or hits a breakpoint or watchpoint, calling `on_tick` every cycle
+    fn run(&mut self, mut on_tick: impl FnMut(&Tick)) -> Stop {
+        loop {
+            let next = self.cycle + 1;
+            if self.breakpoints.contains(&next) && self.resumed_from != Some(next) {
+                self.resumed_from = Some(next);
+                return Stop::Breakpoint(next);
+            }
+            let before = self.registers;
+            match self.step() {
+                None => return Stop::Halted,
+                Some(tick) => on_tick(&tick),
+            }
+            let changed = self
+                .watchpoints
+                .iter()
+                .find(|r| before[r.0] != self.registers[r.0]);
+            if let Some(&register) = changed {
+                return Stop::Watchpoint {
+                    cycle: self.cycle,
+                    register,
+                    old: before[register.0],
+                    new: self.registers[register.0],
+                };
             }
         }
     }
 }
 
-fn main() {
-    let code_lines: Vec<String> = stdin()
-        .lines()
-        .map(|line| line.expect("IO Error"))
+impl Iterator for Cpu {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step()
+    }
+}
+
+/// The columns a sprite `sprite` pixels wide centered on `x` covers, leaning right when its
+/// width is even and empty when it's zero
+fn sprite_span(x: i32, sprite: usize) -> Range<i32> {
+    let end = x + sprite as i32 / 2 + 1;
+    end - sprite as i32..end
+}
+
+pub struct Crt {
+    width: usize,
+    height: usize,
+    sprite: usize,
+    pixels: Vec<bool>,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt::new(40, 6, 3)
+    }
+}
+
+impl Crt {
+    fn new(width: usize, height: usize, sprite: usize) -> Self {
+        Self {
+            width,
+            height,
+            sprite,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Draw the pixel for the cycle, it is lit if the sprite centered on `x` overlaps it
+    fn draw(&mut self, tick: &Tick) {
+        let pos = (tick.cycle - 1) % (self.width * self.height);
+        let column = (pos % self.width) as i32;
+        self.pixels[pos] = sprite_span(tick.x(), self.sprite).contains(&column);
+    }
+
+    fn render(&self) -> String {
+        self.pixels
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|&lit| if lit { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn signal_strength(ticks: &[Tick], cycles_read: &[usize]) -> i32 {
+    ticks
+        .iter()
+        .filter(|tick| cycles_read.contains(&tick.cycle))
+        .map(|tick| tick.cycle as i32 * tick.x())
+        .sum()
+}
+
+fn default_cycles_read() -> Vec<usize> {
+    (20..=220).step_by(40).collect()
+}
+
+/// Find a program that makes the CRT draw `bitmap`, given as rows of `#` for lit and `.` for dark.
+///
+/// `x` can only change when an `addx` completes, so every value is held for at least two cycles,
+/// and the first one is always 1. The search picks, for each cycle a value starts being held in,
+/// how long to hold it and what to change to next.
+fn generate_program(bitmap: &str, sprite: usize) -> Result<Vec<Instruction>> {
+    let rows: Vec<&str> = bitmap.lines().filter(|line| !line.is_empty()).collect();
+    let width = rows.first().map(|row| row.len()).context("Empty bitmap")?;
+    if rows.iter().any(|row| row.len() != width) {
+        return Err(anyhow!("All rows must be {width} pixels wide"));
+    }
+    let pixels: Vec<bool> = rows
+        .iter()
+        .flat_map(|row| row.chars().map(|ch| ch == '#'))
         .collect();
-    let code = code_lines.join("\n");
-    let prog = parse_instructions(code.as_str().lines());
-    let cycles_read = vec![20, 60, 100, 140, 180, 220];
-    let mut sum = 0;
-    let mut display: Vec<&str> = Vec::new();
-
-    for (index, register) in prog.enumerate() {
-        let cycle: i32 = (index + 1) as i32;
-        if cycles_read.contains(&cycle) {
-            sum += cycle * register;
+    let cycles = pixels.len();
+    let lit = |cycle: usize, x: i32| sprite_span(x, sprite).contains(&((cycle % width) as i32));
+    let accepts = |cycle: usize, x: i32| lit(cycle, x) == pixels[cycle];
+
+    // Values outside this range light nothing, so there's no need to consider any others
+    let values: Vec<i32> = (-(sprite as i32)..=(width + sprite) as i32).collect();
+    // next[cycle][value] is how long to hold value from cycle, and what to change to after
+    let mut next: Vec<Vec<Option<(usize, i32)>>> = vec![vec![None; values.len()]; cycles + 1];
+    let mut possible = vec![false; cycles + 1];
+    possible[cycles] = true;
+    for start in (0..cycles).rev() {
+        for (i, &x) in values.iter().enumerate() {
+            let held = (start..cycles).take_while(|&c| accepts(c, x)).count();
+            if start + held == cycles {
+                // Hold it until the end with noops
+                next[start][i] = Some((held, x));
+            } else if let Some(len) = (2..=held).rev().find(|len| possible[start + len]) {
+                let change = values
+                    .iter()
+                    .enumerate()
+                    .find(|(j, _)| next[start + len][*j].is_some())
+                    .map(|(_, &x)| x)
+                    .unwrap();
+                next[start][i] = Some((len, change));
+            }
+            possible[start] |= next[start][i].is_some();
+        }
+    }
+
+    let index = |x: i32| values.iter().position(|&v| v == x).unwrap();
+    let mut program = Vec::new();
+    let (mut cycle, mut x) = (0, 1);
+    while cycle < cycles {
+        let (len, change) = next[cycle][index(x)]
+            .with_context(|| format!("Unable to draw the bitmap, stuck at cycle {}", cycle + 1))?;
+        if cycle + len == cycles {
+            program.extend(std::iter::repeat_n(Instruction::Noop, len));
+        } else {
+            program.extend(std::iter::repeat_n(Instruction::Noop, len - 2));
+            program.push(Instruction::Add(Register::X, change - x));
         }
-        let visible: bool = ((display.len() as i32) - register).abs() <= 1;
-        display.push(if visible { "#" } else { " " });
-        if display.len() == 40 {
-            println!("{}", display.join(""));
-            display.clear();
+        cycle += len;
+        x = change;
+    }
+    Ok(program)
+}
+
+fn main() -> Result<()> {
+    let input = read_stdin()?;
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("generate") => {
+            print!("{}", disassemble(&generate_program(&input, 3)?));
+            return Ok(());
         }
+        Some("format") => {
+            print!("{}", disassemble(&assemble(&input)?));
+            return Ok(());
+        }
+        _ => {}
     }
+    let mut cpu = parse_instructions(input.lines())?;
+    let mut cycles_read = default_cycles_read();
+    let (mut width, mut height, mut sprite) = (40, 6, 3);
 
-    println!("signal strength: {}", sum);
+    // Run `generate` to turn a bitmap into a program or `format` to tidy a program. Otherwise
+    // options are given as `trace`, `width=40`, `height=6`, `sprite=3`, `break=100`,
+    // `watch=x`, `cycles=20,60` or `cost=add:2`
+    for arg in args {
+        let (key, value) = arg.split_once('=').unwrap_or((arg.as_str(), ""));
+        let expected_number = || format!("Expected a number in {arg}");
+        let positive = |value: &str| match value.parse().with_context(expected_number)? {
+            0 => Err(anyhow!("Expected a positive number in {arg}")),
+            n => Ok(n),
+        };
+        match key {
+            "trace" => cpu.trace = true,
+            "width" => width = positive(value)?,
+            "height" => height = positive(value)?,
+            "sprite" => sprite = positive(value)?,
+            "break" => {
+                cpu.breakpoints
+                    .insert(value.parse().with_context(expected_number)?);
+            }
+            "watch" => {
+                let register = value
+                    .chars()
+                    .next()
+                    .and_then(Register::from_name)
+                    .with_context(|| format!("Unknown register in {arg}"))?;
+                cpu.watchpoints.insert(register);
+            }
+            "cost" => {
+                let (op, cycles) = value
+                    .split_once(':')
+                    .with_context(|| format!("Expected op:cycles in {arg}"))?;
+                let op = match op {
+                    "noop" => Op::Noop,
+                    "add" => Op::Add,
+                    _ => return Err(anyhow!("Unknown op {op}")),
+                };
+                let cycles = cycles.parse().with_context(expected_number)?;
+                cpu.table = cpu.table.clone().with_cost(op, cycles);
+            }
+            "cycles" => {
+                cycles_read = value
+                    .split(',')
+                    .map(|c| c.parse().with_context(expected_number))
+                    .collect::<Result<_>>()?
+            }
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
+
+    let mut crt = Crt::new(width, height, sprite);
+    let mut ticks = Vec::new();
+    loop {
+        let stop = cpu.run(|tick| {
+            crt.draw(tick);
+            ticks.push(*tick);
+        });
+        match stop {
+            Stop::Halted => break,
+            Stop::Breakpoint(cycle) => println!(
+                "break before cycle {cycle}: {}",
+                Tick {
+                    cycle,
+                    registers: cpu.registers
+                }
+            ),
+            Stop::Watchpoint {
+                cycle,
+                register,
+                old,
+                new,
+            } => println!(
+                "cycle {cycle}: {} changed from {old} to {new}",
+                register.name()
+            ),
+        }
+    }
+
+    let display = crt.render();
+    println!("{display}");
+    match parse_letters(&display) {
+        Ok(letters) => println!("letters: {letters}"),
+        Err(e) => println!("unable to read letters: {e}"),
+    }
+    println!("signal strength: {}", signal_strength(&ticks, &cycles_read));
+    Ok(())
 }
 
 #[cfg(test)]
@@ -248,39 +647,95 @@ noop
 noop
 ";
 
-    #[cfg(test)]
     #[test]
     fn test_small_example() {
-        let mut prog = parse_instructions(SMALL_EXAMPLE.lines());
-        assert_eq!(prog.next(), Some(1));
-        assert_eq!(prog.next(), Some(1));
-        assert_eq!(prog.next(), Some(1));
-        assert_eq!(prog.next(), Some(4));
-        assert_eq!(prog.next(), Some(4));
-        assert_eq!(prog.next(), Some(-1));
+        let mut prog = parse_instructions(SMALL_EXAMPLE.lines()).unwrap();
+        assert_eq!(prog.next().map(|t| t.x()), Some(1));
+        assert_eq!(prog.next().map(|t| t.x()), Some(1));
+        assert_eq!(prog.next().map(|t| t.x()), Some(1));
+        assert_eq!(prog.next().map(|t| t.x()), Some(4));
+        assert_eq!(prog.next().map(|t| t.x()), Some(4));
+        assert_eq!(prog.registers[Register::X.0], -1);
         assert_eq!(prog.next(), None);
     }
 
     #[test]
     fn test_large_example() {
-        let prog = parse_instructions(LARGE_EXAMPLE.lines());
-        let cycles_read = vec![20, 60, 100, 140, 180, 220];
-        let mut sum = 0;
-
-        for (index, register) in prog.enumerate() {
-            let cycle: i32 = (index + 1) as i32;
-            if cycles_read.contains(&cycle) {
-                println!(
-                    "cycle: {} register: {} signal: {}",
-                    cycle,
-                    register,
-                    cycle * register
-                );
-                sum += cycle * register;
-            }
+        let prog = parse_instructions(LARGE_EXAMPLE.lines()).unwrap();
+        let ticks: Vec<_> = prog.collect();
+        assert_eq!(signal_strength(&ticks, &default_cycles_read()), 13140);
+    }
+
+    #[test]
+    fn test_crt_example() {
+        let mut crt = Crt::default();
+        for tick in parse_instructions(LARGE_EXAMPLE.lines()).unwrap() {
+            crt.draw(&tick);
+        }
+        let expected = "##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....";
+        assert_eq!(crt.render(), expected);
+    }
+
+    #[test]
+    fn test_sprite_span() {
+        assert_eq!(sprite_span(5, 3), 4..7);
+        assert_eq!(sprite_span(5, 4), 4..8);
+        assert_eq!(sprite_span(5, 1), 5..6);
+        assert!(sprite_span(5, 0).is_empty());
+        let mut crt = Crt::new(4, 1, 0);
+        for tick in parse_instructions(SMALL_EXAMPLE.lines()).unwrap().take(4) {
+            crt.draw(&tick);
         }
+        assert_eq!(crt.render(), "....");
+    }
 
-        assert_eq!(sum, 13140);
+    #[test]
+    fn test_instruction_table_and_registers() {
+        let table = InstructionTable::default().with_cost(Op::Add, 3);
+        let program = vec![
+            parse_instruction("addy 5").unwrap(),
+            parse_instruction("noop").unwrap(),
+            parse_instruction("addx 2").unwrap(),
+        ];
+        let ticks: Vec<_> = Cpu::new(program, table).collect();
+        assert_eq!(ticks.len(), 7);
+        assert_eq!(ticks[3].register(Register(1)), 6);
+        assert_eq!(ticks[6].x(), 1);
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut cpu = parse_instructions(SMALL_EXAMPLE.lines()).unwrap();
+        cpu.breakpoints.insert(4);
+        cpu.watchpoints.insert(Register::X);
+        let mut seen = 0;
+        assert_eq!(
+            cpu.run(|_| seen += 1),
+            Stop::Watchpoint {
+                cycle: 3,
+                register: Register::X,
+                old: 1,
+                new: 4
+            }
+        );
+        assert_eq!(cpu.run(|_| seen += 1), Stop::Breakpoint(4));
+        assert_eq!(cpu.registers[Register::X.0], 4);
+        assert_eq!(
+            cpu.run(|_| seen += 1),
+            Stop::Watchpoint {
+                cycle: 5,
+                register: Register::X,
+                old: 4,
+                new: -1
+            }
+        );
+        assert_eq!(cpu.run(|_| seen += 1), Stop::Halted);
+        assert_eq!(seen, 5);
     }
 
     #[test]
@@ -288,7 +743,81 @@ noop
         let _lines: Vec<Instruction> = SMALL_EXAMPLE
             .lines()
             .filter(|l| !l.is_empty())
-            .map(parse_instruction)
+            .map(|l| parse_instruction(l).unwrap())
             .collect();
     }
+
+    #[test]
+    fn test_round_trip() {
+        let program = assemble(LARGE_EXAMPLE).unwrap();
+        assert_eq!(disassemble(&program), LARGE_EXAMPLE);
+        let program = assemble("addy -3\n\nnoop\naddw 7\n").unwrap();
+        assert_eq!(disassemble(&program), "addy -3\nnoop\naddw 7\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(
+            error("noop\nmulx 3\n"),
+            "line 2: unknown instruction `mulx`"
+        );
+        assert_eq!(
+            error("noop\n\naddx\n"),
+            "line 3: missing operand for `addx`"
+        );
+        assert_eq!(error("addx q"), "line 1: bad operand `q` for `addx`");
+        assert_eq!(error("addq 1"), "line 1: unknown register in `addq`");
+        assert_eq!(error("noop 1"), "line 1: unexpected `1` after `noop`");
+    }
+
+    #[test]
+    fn test_generate_letters() {
+        let bitmap = aoc::ocr::render_letters("PEKZFEHG").unwrap();
+        let program = generate_program(&bitmap, 3).unwrap();
+        let mut crt = Crt::default();
+        for tick in Cpu::new(program, InstructionTable::default()) {
+            crt.draw(&tick);
+        }
+        assert_eq!(crt.render(), bitmap);
+        assert_eq!(parse_letters(&crt.render()).unwrap(), "PEKZFEHG");
+    }
+
+    #[test]
+    fn test_generate_example() {
+        let mut crt = Crt::default();
+        for tick in parse_instructions(LARGE_EXAMPLE.lines()).unwrap() {
+            crt.draw(&tick);
+        }
+        let bitmap = crt.render();
+        let mut regenerated = Crt::default();
+        for tick in Cpu::new(
+            generate_program(&bitmap, 3).unwrap(),
+            InstructionTable::default(),
+        ) {
+            regenerated.draw(&tick);
+        }
+        assert_eq!(regenerated.render(), bitmap);
+    }
+
+    #[test]
+    fn test_generate_other_sprites() {
+        // The generator and the CRT have to agree on what a sprite covers
+        for sprite in [1, 2, 4] {
+            let draw = |cpu: Cpu| {
+                let mut crt = Crt::new(40, 6, sprite);
+                cpu.for_each(|tick| crt.draw(&tick));
+                crt.render()
+            };
+            let bitmap = draw(parse_instructions(LARGE_EXAMPLE.lines()).unwrap());
+            let program = generate_program(&bitmap, sprite).unwrap();
+            assert_eq!(draw(Cpu::new(program, InstructionTable::default())), bitmap);
+        }
+    }
+
+    #[test]
+    fn test_generate_impossible() {
+        // x starts at 1, so the first pixel is always lit
+        assert!(generate_program(".#\n##\n", 3).is_err());
+    }
 }
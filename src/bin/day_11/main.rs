@@ -1,59 +1,248 @@
+use anyhow::{anyhow, Context, Result};
+use aoc::io::read_stdin;
+use itertools::Itertools;
 use std::collections::HashMap;
-use std::io::stdin;
 
-#[derive(PartialEq, Debug)]
+/// The expression after `new =`, evaluated with `old` as the current worry level
+#[derive(PartialEq, Debug, Clone)]
 enum Operation {
-    Add(u64),
-    Mul(u64),
-    Square(),
+    Old,
+    Const(u64),
+    Add(Box<Operation>, Box<Operation>),
+    Sub(Box<Operation>, Box<Operation>),
+    Mul(Box<Operation>, Box<Operation>),
+    Div(Box<Operation>, Box<Operation>),
+    Rem(Box<Operation>, Box<Operation>),
+}
+
+impl Operation {
+    /// Evaluate with checked arithmetic, `None` on overflow, underflow or division by zero
+    fn eval(&self, old: u64) -> Option<u64> {
+        match self {
+            Operation::Old => Some(old),
+            Operation::Const(c) => Some(*c),
+            Operation::Add(l, r) => l.eval(old)?.checked_add(r.eval(old)?),
+            Operation::Sub(l, r) => l.eval(old)?.checked_sub(r.eval(old)?),
+            Operation::Mul(l, r) => l.eval(old)?.checked_mul(r.eval(old)?),
+            Operation::Div(l, r) => l.eval(old)?.checked_div(r.eval(old)?),
+            Operation::Rem(l, r) => l.eval(old)?.checked_rem(r.eval(old)?),
+        }
+    }
+
+    /// Whether evaluating modulo `base` gives the same result modulo `base` as evaluating
+    /// the full value. Division breaks this, as does `%` unless it divides `base`, and so does
+    /// subtraction, which would wrap around instead of failing when the full value goes negative.
+    fn reducible(&self, base: u64) -> bool {
+        match self {
+            Operation::Old | Operation::Const(_) => true,
+            Operation::Add(l, r) | Operation::Mul(l, r) => l.reducible(base) && r.reducible(base),
+            Operation::Sub(_, _) | Operation::Div(_, _) => false,
+            Operation::Rem(l, r) => {
+                l.reducible(base)
+                    && matches!(**r, Operation::Const(c) if c != 0 && base.is_multiple_of(c))
+            }
+        }
+    }
+
+    /// Evaluate modulo `base`, only meaningful when `reducible(base)` holds
+    fn eval_mod(&self, old: u64, base: u64) -> u64 {
+        let base = base as u128;
+        let eval = |op: &Operation| op.eval_mod(old, base as u64) as u128;
+        let result = match self {
+            Operation::Old => old as u128 % base,
+            Operation::Const(c) => *c as u128 % base,
+            Operation::Add(l, r) => (eval(l) + eval(r)) % base,
+            Operation::Sub(l, r) => (eval(l) + base - eval(r)) % base,
+            Operation::Mul(l, r) => (eval(l) * eval(r)) % base,
+            Operation::Div(l, r) => eval(l) / eval(r),
+            // `reducible` only allows constants dividing `base`, which may be `base` itself,
+            // so the divisor is used as it is rather than reduced to 0
+            Operation::Rem(l, r) => match **r {
+                Operation::Const(c) => eval(l) % c as u128,
+                _ => eval(l) % eval(r),
+            },
+        };
+        result as u64
+    }
 }
 
 #[derive(PartialEq, Debug)]
 struct Monkey {
     id: usize,
     items: Vec<u64>,
+    /// Identifies the items across monkeys, in the same order as `items`
+    item_ids: Vec<usize>,
     op: Operation,
     divides_by: u64,
     pos_monkey: usize,
     neg_monkey: usize,
 }
 
-const MONKEY_RE: &str = r"Monkey ([0-9]+):\n  Starting items: (.+)\n  Operation: new = (.+)\n  Test: divisible by ([0-9]+)\n    If true: throw to monkey ([0-9]+)\n    If false: throw to monkey ([0-9]+)";
-
-fn parse_monkey_block(monkey: &str) -> Option<Monkey> {
-    let re = regex::Regex::new(MONKEY_RE).ok()?;
-    let groups = re.captures(monkey)?;
-    let id: usize = groups.get(1)?.as_str().parse().ok()?;
-
-    let starting_items_text: &str = groups.get(2)?.as_str();
-    let items: Vec<u64> = starting_items_text
-        .split(", ")
-        .filter_map(|it| it.parse().ok())
-        .collect();
-
-    let op_text = groups.get(3)?.as_str();
-    let op = if op_text == "old * old" {
-        Some(Operation::Square())
-    } else {
-        let mut parts = op_text.split(' ');
-        parts.next();
-        let operator = parts.next()?;
-        let operand = parts.next()?.parse().ok()?;
-        if operator == "*" {
-            Some(Operation::Mul(operand))
-        } else if operator == "+" {
-            Some(Operation::Add(operand))
+/// Reads a single line, keeping track of where it is for error messages
+struct Cursor<'a> {
+    line_no: usize,
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line_no: usize, line: &'a str) -> Self {
+        Self {
+            line_no,
+            line,
+            pos: 0,
+        }
+    }
+
+    fn error(&self, message: String) -> anyhow::Error {
+        anyhow!("line {}, column {}: {message}", self.line_no, self.pos + 1)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        self.pos += self.rest().len() - self.rest().trim_start_matches(' ').len();
+    }
+
+    fn expect(&mut self, text: &str) -> Result<()> {
+        if self.rest().starts_with(text) {
+            self.pos += text.len();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{text}`")))
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T> {
+        let digits = self.rest().len()
+            - self
+                .rest()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let number = self.rest()[..digits]
+            .parse()
+            .map_err(|_| self.error("expected a number".to_string()))?;
+        self.pos += digits;
+        Ok(number)
+    }
+
+    fn end(&self) -> Result<()> {
+        if self.rest().is_empty() {
+            Ok(())
         } else {
-            None
+            Err(self.error(format!("unexpected `{}`", self.rest())))
         }
-    }?;
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Operation> {
+        let mut left = self.term()?;
+        loop {
+            self.skip_spaces();
+            let combine = match self.rest().chars().next() {
+                Some('+') => Operation::Add,
+                Some('-') => Operation::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = combine(Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    /// term := atom (('*' | '/' | '%') atom)*
+    fn term(&mut self) -> Result<Operation> {
+        let mut left = self.atom()?;
+        loop {
+            self.skip_spaces();
+            let combine = match self.rest().chars().next() {
+                Some('*') => Operation::Mul,
+                Some('/') => Operation::Div,
+                Some('%') => Operation::Rem,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = combine(Box::new(left), Box::new(self.atom()?));
+        }
+    }
+
+    /// atom := 'old' | number | '(' expr ')'
+    fn atom(&mut self) -> Result<Operation> {
+        self.skip_spaces();
+        match self.rest().chars().next() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.skip_spaces();
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() => Ok(Operation::Const(self.number()?)),
+            _ if self.rest().starts_with("old") => {
+                self.pos += 3;
+                Ok(Operation::Old)
+            }
+            _ => Err(self.error("expected `old`, a number or `(`".to_string())),
+        }
+    }
+}
+
+/// Parse the six lines describing a monkey, where the first one is line `first_line` of the input
+fn parse_monkey_block(monkey: &str, first_line: usize) -> Result<Monkey> {
+    let mut lines = monkey
+        .lines()
+        .enumerate()
+        .map(|(n, line)| Cursor::new(n + first_line, line));
+    let mut line_no = first_line - 1;
+    let mut next_line = |what: &str| {
+        let line = lines
+            .next()
+            .with_context(|| format!("line {}: expected {what}", line_no + 1))?;
+        line_no = line.line_no;
+        Ok::<_, anyhow::Error>(line)
+    };
+
+    let mut line = next_line("a monkey")?;
+    line.expect("Monkey ")?;
+    let id = line.number()?;
+    line.expect(":")?;
+    line.end()?;
+
+    let mut line = next_line("starting items")?;
+    line.expect("  Starting items: ")?;
+    let mut items = vec![line.number()?];
+    while line.expect(", ").is_ok() {
+        items.push(line.number()?);
+    }
+    line.end()?;
 
-    let divides_by: u64 = groups.get(4)?.as_str().parse().ok()?;
-    let pos_monkey: usize = groups.get(5)?.as_str().parse().ok()?;
-    let neg_monkey: usize = groups.get(6)?.as_str().parse().ok()?;
+    let mut line = next_line("an operation")?;
+    line.expect("  Operation: new = ")?;
+    let op = line.expr()?;
+    line.end()?;
 
-    Some(Monkey {
+    let mut line = next_line("a test")?;
+    line.expect("  Test: divisible by ")?;
+    let divides_by = line.number()?;
+    if divides_by == 0 {
+        return Err(line.error("can't test for divisibility by 0".to_string()));
+    }
+    line.end()?;
+
+    let mut line = next_line("a true branch")?;
+    line.expect("    If true: throw to monkey ")?;
+    let pos_monkey = line.number()?;
+    line.end()?;
+
+    let mut line = next_line("a false branch")?;
+    line.expect("    If false: throw to monkey ")?;
+    let neg_monkey = line.number()?;
+    line.end()?;
+
+    Ok(Monkey {
         id,
+        item_ids: (0..items.len()).collect(),
         items,
         op,
         divides_by,
@@ -62,15 +251,183 @@ fn parse_monkey_block(monkey: &str) -> Option<Monkey> {
     })
 }
 
-fn parse_monkeys(text: &str) -> Vec<Monkey> {
-    text.split("\n\n").filter_map(parse_monkey_block).collect()
+fn parse_monkeys(text: &str) -> Result<Vec<Monkey>> {
+    let mut monkeys = Vec::new();
+    let mut first_line = 1;
+    for block in text.split("\n\n") {
+        if !block.trim().is_empty() {
+            monkeys.push(parse_monkey_block(block, first_line)?);
+        }
+        first_line += block.lines().count() + 1;
+    }
+
+    let mut next_id = 0;
+    for monkey in monkeys.iter_mut() {
+        monkey.item_ids = (next_id..next_id + monkey.items.len()).collect();
+        next_id += monkey.items.len();
+    }
+
+    for (index, monkey) in monkeys.iter().enumerate() {
+        if monkey.id != index {
+            return Err(anyhow!(
+                "Expected monkey {index}, found monkey {}",
+                monkey.id
+            ));
+        }
+        for target in [monkey.pos_monkey, monkey.neg_monkey] {
+            if target >= monkeys.len() || target == index {
+                return Err(anyhow!("Monkey {index} can't throw to monkey {target}"));
+            }
+        }
+    }
+    Ok(monkeys)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+struct Throw {
+    round: usize,
+    from: usize,
+    to: usize,
+    worry: u64,
+}
+
+/// Everything that happened to the items, recorded round by round
+#[derive(Default, Debug)]
+struct Trace {
+    /// The starting monkey and worry level of each item
+    start: Vec<(usize, u64)>,
+    /// Every throw of each item
+    paths: Vec<Vec<Throw>>,
+    /// Total inspections per monkey after each round
+    inspections: Vec<Vec<usize>>,
+    /// Worry levels of the items each monkey holds after each round
+    holdings: Vec<Vec<Vec<u64>>>,
+}
+
+impl Trace {
+    fn new(monkeys: &[Monkey]) -> Self {
+        let mut start = Vec::new();
+        for monkey in monkeys {
+            for (&id, &worry) in monkey.item_ids.iter().zip(monkey.items.iter()) {
+                if start.len() <= id {
+                    start.resize(id + 1, (0, 0));
+                }
+                start[id] = (monkey.id, worry);
+            }
+        }
+        Self {
+            paths: vec![Vec::new(); start.len()],
+            start,
+            ..Default::default()
+        }
+    }
+
+    fn end_round(&mut self, monkeys: &[Monkey], counter: &HashMap<usize, usize>) {
+        self.inspections.push(
+            (0..monkeys.len())
+                .map(|id| counter.get(&id).copied().unwrap_or(0))
+                .collect(),
+        );
+        self.holdings
+            .push(monkeys.iter().map(|m| m.items.clone()).collect());
+    }
+
+    /// What each monkey holds after `round`, like the tables in the puzzle text
+    fn holdings_after(&self, round: usize) -> Option<String> {
+        let holdings = self.holdings.get(round.checked_sub(1)?)?;
+        Some(
+            holdings
+                .iter()
+                .enumerate()
+                .map(|(id, items)| format!("Monkey {id}: {}\n", items.iter().join(", ")))
+                .collect(),
+        )
+    }
+
+    /// How many items each monkey has inspected after `round`, like in the puzzle text
+    fn inspections_after(&self, round: usize) -> Option<String> {
+        let inspections = self.inspections.get(round.checked_sub(1)?)?;
+        Some(
+            inspections
+                .iter()
+                .enumerate()
+                .map(|(id, count)| format!("Monkey {id} inspected items {count} times.\n"))
+                .collect(),
+        )
+    }
+
+    /// Inspections per monkey in each round, one row per round
+    fn inspections_csv(&self) -> String {
+        let monkeys = self.inspections.first().map_or(0, |row| row.len());
+        let mut out = format!(
+            "round,{}\n",
+            (0..monkeys).map(|id| format!("monkey {id}")).join(",")
+        );
+        let mut previous = vec![0; monkeys];
+        for (round, totals) in self.inspections.iter().enumerate() {
+            let counts = totals.iter().zip(previous.iter()).map(|(t, p)| t - p);
+            out.push_str(&format!("{},{}\n", round + 1, counts.format(",")));
+            previous = totals.clone();
+        }
+        out
+    }
+
+    /// The monkeys each item passed through and its worry level along the way
+    fn item_report(&self) -> String {
+        self.paths
+            .iter()
+            .enumerate()
+            .map(|(id, throws)| {
+                let (monkey, worry) = self.start[id];
+                let path = throws
+                    .iter()
+                    .map(|t| format!(" -> {} ({}) in round {}", t.to, t.worry, t.round));
+                format!("item {id}: {monkey} ({worry}){}\n", path.format(""))
+            })
+            .collect()
+    }
+}
+
+/// What keeps worry levels in check after an inspection
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Relief {
+    /// Part 1, worry levels are divided by three
+    DivideByThree,
+    /// Part 2, worry levels are kept modulo the product of all the divisors
+    Reduce(u64),
+    /// Part 2 with operations that can't be reduced, so worry levels grow until they overflow
+    Unbounded,
 }
 
-fn new_value(old: u64, op: &Operation) -> u64 {
-    match op {
-        Operation::Square() => old * old,
-        Operation::Mul(arg) => old * arg,
-        Operation::Add(arg) => old + arg,
+impl Relief {
+    fn new(monkeys: &[Monkey], modulo: bool) -> Relief {
+        // Divisors whose product doesn't fit leave nothing to reduce by
+        let base = monkeys
+            .iter()
+            .try_fold(1u64, |product, m| product.checked_mul(m.divides_by));
+        match base {
+            _ if !modulo => Relief::DivideByThree,
+            // Reducing is only correct if every monkey computes the same thing modulo base
+            Some(base) if monkeys.iter().all(|m| m.op.reducible(base)) => Relief::Reduce(base),
+            _ => Relief::Unbounded,
+        }
+    }
+
+    /// The worry level after `monkey` inspects the item
+    fn inspect(&self, monkey: &Monkey, item: u64) -> Result<u64> {
+        let checked = || {
+            monkey.op.eval(item).with_context(|| {
+                format!(
+                    "Monkey {} overflowed inspecting an item with worry level {item}",
+                    monkey.id
+                )
+            })
+        };
+        Ok(match self {
+            Relief::DivideByThree => checked()? / 3,
+            Relief::Reduce(base) => monkey.op.eval_mod(item, *base),
+            Relief::Unbounded => checked()?,
+        })
     }
 }
 
@@ -78,59 +435,210 @@ fn do_monkey_turn(
     monkey_id: usize,
     monkeys: &mut [Monkey],
     counter: &mut HashMap<usize, usize>,
-    modulo: bool,
-) {
-    let base = monkeys.iter().fold(1, |product, m| product * m.divides_by);
-
+    relief: Relief,
+    mut trace: Option<&mut Trace>,
+) -> Result<()> {
     let monkey = &mut monkeys[monkey_id];
-    let mut moves: Vec<(usize, u64)> = Vec::new();
+    let mut moves: Vec<(usize, u64, usize)> = Vec::new();
 
-    for item in &monkey.items {
-        let new_worry = new_value(*item, &monkey.op);
-        let worry = if modulo {
-            new_worry % base
-        } else {
-            new_worry / 3
-        };
+    for (item, &item_id) in monkey.items.iter().zip(monkey.item_ids.iter()) {
+        let worry = relief.inspect(monkey, *item)?;
         let target_monkey = if worry % monkey.divides_by == 0 {
             monkey.pos_monkey
         } else {
             monkey.neg_monkey
         };
         *counter.entry(monkey_id).or_insert(0) += 1;
-        moves.push((target_monkey, worry));
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.paths[item_id].push(Throw {
+                round: trace.holdings.len() + 1,
+                from: monkey_id,
+                to: target_monkey,
+                worry,
+            });
+        }
+        moves.push((target_monkey, worry, item_id));
     }
     monkey.items.clear();
+    monkey.item_ids.clear();
 
-    for (target_monkey, item) in moves {
+    for (target_monkey, item, item_id) in moves {
         monkeys[target_monkey].items.push(item);
+        monkeys[target_monkey].item_ids.push(item_id);
     }
+    Ok(())
 }
 
-fn do_monkey_round(monkeys: &mut Vec<Monkey>, counter: &mut HashMap<usize, usize>, modulo: bool) {
+fn do_monkey_round(
+    monkeys: &mut [Monkey],
+    counter: &mut HashMap<usize, usize>,
+    relief: Relief,
+    mut trace: Option<&mut Trace>,
+) -> Result<()> {
     for monkey_id in 0..monkeys.len() {
-        do_monkey_turn(monkey_id, monkeys, counter, modulo);
+        do_monkey_turn(monkey_id, monkeys, counter, relief, trace.as_deref_mut())?;
+    }
+    if let Some(trace) = trace {
+        trace.end_round(monkeys, counter);
+    }
+    Ok(())
+}
+
+/// Follow a single item through one round, starting with `monkey` holding it. Items thrown
+/// to a monkey later in the round get inspected again in the same round.
+fn item_round(
+    monkeys: &[Monkey],
+    relief: Relief,
+    (mut monkey, mut worry): (usize, u64),
+    counts: &mut [u64],
+) -> Result<(usize, u64)> {
+    loop {
+        let current = &monkeys[monkey];
+        worry = relief.inspect(current, worry)?;
+        counts[monkey] += 1;
+        let target = if worry % current.divides_by == 0 {
+            current.pos_monkey
+        } else {
+            current.neg_monkey
+        };
+        let waits = target < monkey;
+        monkey = target;
+        if waits {
+            return Ok((monkey, worry));
+        }
+    }
+}
+
+/// Inspections per monkey after `rounds`, simulating each item on its own.
+///
+/// Items never affect each other, and an item's (monkey, worry) state at the start of a
+/// round decides everything that happens to it after. With reduced worry levels there are
+/// finitely many states, so every item eventually repeats one and the rest of its rounds
+/// can be counted by multiplying up the cycle.
+fn inspections_by_cycles(monkeys: &[Monkey], relief: Relief, rounds: u64) -> Result<Vec<u64>> {
+    let n = monkeys.len();
+    let mut totals = vec![0; n];
+    for monkey in monkeys {
+        for &item in monkey.items.iter() {
+            let mut seen: HashMap<(usize, u64), usize> = HashMap::new();
+            // Inspections per monkey in the first `r` rounds is in `prefix[r]`
+            let mut prefix: Vec<Vec<u64>> = vec![vec![0; n]];
+            let mut state = (monkey.id, item);
+            let counts = loop {
+                let round = prefix.len() - 1;
+                if round as u64 == rounds {
+                    break prefix[round].clone();
+                }
+                if let Some(&start) = seen.get(&state) {
+                    let length = (round - start) as u64;
+                    let remaining = rounds - round as u64;
+                    let rest = start + (remaining % length) as usize;
+                    break (0..n)
+                        .map(|m| {
+                            let per_cycle = prefix[round][m] - prefix[start][m];
+                            prefix[round][m]
+                                + (remaining / length) * per_cycle
+                                + (prefix[rest][m] - prefix[start][m])
+                        })
+                        .collect();
+                }
+                seen.insert(state, round);
+                let mut counts = prefix[round].clone();
+                state = item_round(monkeys, relief, state, &mut counts)?;
+                prefix.push(counts);
+            };
+            for (total, count) in totals.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+    }
+    Ok(totals)
+}
+
+fn fast_monkey_game(input: &str, rounds: u64, modulo: bool) -> Result<u128> {
+    let monkeys = parse_monkeys(input)?;
+    let relief = Relief::new(&monkeys, modulo);
+    let mut counts = inspections_by_cycles(&monkeys, relief, rounds)?;
+    counts.sort();
+    if counts.len() < 2 {
+        return Err(anyhow!("Need at least two monkeys inspecting items"));
     }
+    Ok(counts[counts.len() - 1] as u128 * counts[counts.len() - 2] as u128)
 }
 
-fn monkey_game(input: &str, rounds: usize, modulo: bool) -> usize {
-    let mut monkeys = parse_monkeys(input);
+fn play(input: &str, rounds: usize, modulo: bool, mut trace: Option<&mut Trace>) -> Result<usize> {
+    let mut monkeys = parse_monkeys(input)?;
+    if let Some(trace) = trace.as_deref_mut() {
+        *trace = Trace::new(&monkeys);
+    }
+    let relief = Relief::new(&monkeys, modulo);
     let mut counter = HashMap::new();
     for _ in 0..rounds {
-        do_monkey_round(&mut monkeys, &mut counter, modulo);
+        do_monkey_round(&mut monkeys, &mut counter, relief, trace.as_deref_mut())?;
     }
     let mut counts: Vec<usize> = counter.values().cloned().collect();
     counts.sort();
-    counts[counts.len() - 1] * counts[counts.len() - 2]
+    if counts.len() < 2 {
+        return Err(anyhow!("Need at least two monkeys inspecting items"));
+    }
+    Ok(counts[counts.len() - 1] * counts[counts.len() - 2])
 }
 
-fn main() {
-    let lines: Vec<String> = stdin().lines().filter_map(|line| line.ok()).collect();
-    let inp = lines.join("\n");
-    let monkey_business = monkey_game(inp.as_str(), 20, false);
-    println!("Part 1: {}", monkey_business);
-    let monkey_business_2 = monkey_game(inp.as_str(), 10000, true);
-    println!("Part 2: {}", monkey_business_2);
+fn monkey_game(input: &str, rounds: usize, modulo: bool) -> Result<usize> {
+    play(input, rounds, modulo, None)
+}
+
+fn traced_monkey_game(input: &str, rounds: usize, modulo: bool) -> Result<(usize, Trace)> {
+    let mut trace = Trace::default();
+    let monkey_business = play(input, rounds, modulo, Some(&mut trace))?;
+    Ok((monkey_business, trace))
+}
+
+fn main() -> Result<()> {
+    let inp = read_stdin()?;
+    let mut args = std::env::args().skip(1);
+    let Some(report) = args.next() else {
+        let monkey_business = monkey_game(inp.as_str(), 20, false)?;
+        println!("Part 1: {}", monkey_business);
+        let monkey_business_2 = monkey_game(inp.as_str(), 10000, true)?;
+        println!("Part 2: {}", monkey_business_2);
+        return Ok(());
+    };
+
+    // Reports are `holdings`, `inspections`, `csv`, `items` or `fast` for the monkey business
+    // counted by cycle detection, followed by `modulo` to use part 2 rules and `rounds=N` to
+    // pick how many rounds to play
+    let (mut modulo, mut rounds) = (false, None);
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("rounds", n)) => rounds = Some(n.parse().context("Expected a number of rounds")?),
+            _ if arg == "modulo" => modulo = true,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
+    let rounds = rounds.unwrap_or(if modulo { 10000 } else { 20 });
+    if report == "fast" {
+        println!("{}", fast_monkey_game(inp.as_str(), rounds as u64, modulo)?);
+        return Ok(());
+    }
+    let (_, trace) = traced_monkey_game(inp.as_str(), rounds, modulo)?;
+    match report.as_str() {
+        "holdings" | "inspections" => {
+            for round in 1..=rounds {
+                println!("== After round {round} ==");
+                let table = if report == "holdings" {
+                    trace.holdings_after(round)
+                } else {
+                    trace.inspections_after(round)
+                };
+                println!("{}", table.unwrap_or_default());
+            }
+        }
+        "csv" => print!("{}", trace.inspections_csv()),
+        "items" => print!("{}", trace.item_report()),
+        _ => return Err(anyhow!("Unknown report {report}")),
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -168,12 +676,15 @@ Monkey 3:
     #[test]
     fn test_parse_monkey_block() {
         let input = EXAMPLE.split("\n\n").next().unwrap();
-        let maybe_monkey = parse_monkey_block(input);
-        assert!(maybe_monkey.is_some());
+        let maybe_monkey = parse_monkey_block(input, 1);
+        assert!(maybe_monkey.is_ok());
         let monkey = maybe_monkey.unwrap();
         assert_eq!(monkey.id, 0);
         assert_eq!(monkey.items, vec![79, 98]);
-        assert_eq!(monkey.op, Operation::Mul(19));
+        assert_eq!(
+            monkey.op,
+            Operation::Mul(Box::new(Operation::Old), Box::new(Operation::Const(19)))
+        );
         assert_eq!(monkey.divides_by, 23);
         assert_eq!(monkey.pos_monkey, 2);
         assert_eq!(monkey.neg_monkey, 3);
@@ -181,20 +692,229 @@ Monkey 3:
 
     #[test]
     fn test_parse_monkeys() {
-        let monkeys = parse_monkeys(EXAMPLE);
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
         assert_eq!(monkeys.len(), 4);
     }
 
     #[test]
     fn test_monkey_turn() {
-        let mut monkeys = parse_monkeys(EXAMPLE);
-        do_monkey_turn(0, &mut monkeys, &mut HashMap::new(), false);
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        do_monkey_turn(
+            0,
+            &mut monkeys,
+            &mut HashMap::new(),
+            Relief::DivideByThree,
+            None,
+        )
+        .unwrap();
         println!("{:?}", monkeys[0])
     }
 
     #[test]
     fn test_monkey_game() {
-        let monkey_business = monkey_game(EXAMPLE, 20, false);
+        let monkey_business = monkey_game(EXAMPLE, 20, false).unwrap();
         assert_eq!(monkey_business, 10605);
     }
+
+    #[test]
+    fn test_monkey_game_modulo() {
+        let monkey_business = monkey_game(EXAMPLE, 10000, true).unwrap();
+        assert_eq!(monkey_business, 2713310158);
+    }
+
+    fn parse_op(text: &str) -> Result<Operation> {
+        Cursor::new(1, text).expr()
+    }
+
+    #[test]
+    fn test_expressions() {
+        let op = parse_op("old * 3 + old").unwrap();
+        assert_eq!(op.eval(5), Some(20));
+        assert_eq!(parse_op("old - 2").unwrap().eval(5), Some(3));
+        assert_eq!(parse_op("old - 2").unwrap().eval(1), None);
+        assert_eq!(parse_op("old % 7").unwrap().eval(20), Some(6));
+        assert_eq!(parse_op("(old + 1) * (old+2)").unwrap().eval(2), Some(12));
+        assert_eq!(parse_op("old * old").unwrap().eval(u64::MAX), None);
+        assert!(op.reducible(30));
+        assert!(parse_op("old % 5").unwrap().reducible(30));
+        assert!(!parse_op("old % 7").unwrap().reducible(30));
+        assert!(!parse_op("old / 2").unwrap().reducible(30));
+        assert!(!parse_op("old - 2").unwrap().reducible(30));
+        let op = parse_op("old % 7").unwrap();
+        assert!(op.reducible(7));
+        assert_eq!(op.eval_mod(20, 7), 6);
+        let op = parse_op("old * old + 5 + old % 3").unwrap();
+        for old in 0..100 {
+            assert_eq!(
+                op.eval_mod(old, 30),
+                op.eval(old + 30).map(|v| v % 30).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| parse_monkeys(text).unwrap_err().to_string();
+        let broken = EXAMPLE.replace("old + 6", "old ^ 6");
+        assert_eq!(error(&broken), "line 10, column 24: unexpected `^ 6`");
+        let broken = EXAMPLE.replace("items: 74", "items: 74,");
+        assert_eq!(error(&broken), "line 23, column 21: unexpected `,`");
+        let broken = EXAMPLE.replace("new = old * 19", "new = * 19");
+        assert_eq!(
+            error(&broken),
+            "line 3, column 20: expected `old`, a number or `(`"
+        );
+        let broken = EXAMPLE.replace("    If false: throw to monkey 0\n", "");
+        assert_eq!(error(&broken), "line 13: expected a false branch");
+        let broken = EXAMPLE.replace("throw to monkey 1\n", "throw to monkey 7\n");
+        assert_eq!(error(&broken), "Monkey 2 can't throw to monkey 7");
+    }
+
+    #[test]
+    fn test_remainder_by_whole_base() {
+        // With divisors 7 and 1 the base is 7, the same as the constant in `old % 7`
+        let text = EXAMPLE
+            .replace("old * 19", "old % 7")
+            .replace("by 23", "by 7")
+            .replace("by 19", "by 1")
+            .replace("by 13", "by 1")
+            .replace("by 17", "by 1");
+        let monkeys = parse_monkeys(&text).unwrap();
+        assert_eq!(Relief::new(&monkeys, true), Relief::Reduce(7));
+        assert!(monkey_game(&text, 20, true).is_ok());
+    }
+
+    #[test]
+    fn test_huge_divisors() {
+        let text = EXAMPLE
+            .replace("by 23", "by 1000003")
+            .replace("by 19", "by 1000033")
+            .replace("by 13", "by 1000037")
+            .replace("by 17", "by 1000039");
+        let monkeys = parse_monkeys(&text).unwrap();
+        assert_eq!(Relief::new(&monkeys, true), Relief::Unbounded);
+        assert!(monkey_game(&text, 3, true).is_ok());
+    }
+
+    #[test]
+    fn test_reports_overflowing_monkey() {
+        // Division means worry levels can't be reduced, so they grow until they don't fit
+        let dividing = EXAMPLE.replace("old * 19", "old * 19 / 1");
+        let error = monkey_game(&dividing, 10000, true).unwrap_err().to_string();
+        assert!(error.starts_with("Monkey 0 overflowed inspecting an item with worry level"));
+        // Subtraction is checked too rather than wrapping around modulo the divisors
+        let subtracting = EXAMPLE.replace("old * 19", "old - 80");
+        let monkeys = parse_monkeys(&subtracting).unwrap();
+        assert_eq!(Relief::new(&monkeys, true), Relief::Unbounded);
+        let error = monkey_game(&subtracting, 1, true).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Monkey 0 overflowed inspecting an item with worry level 79"
+        );
+    }
+
+    #[test]
+    fn test_holdings_match_puzzle() {
+        let (_, trace) = traced_monkey_game(EXAMPLE, 20, false).unwrap();
+        assert_eq!(
+            trace.holdings_after(1).unwrap(),
+            "Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: \nMonkey 3: \n"
+        );
+        assert_eq!(
+            trace.holdings_after(20).unwrap(),
+            "Monkey 0: 10, 12, 14, 26, 34
+Monkey 1: 245, 93, 53, 199, 115
+Monkey 2: \nMonkey 3: \n"
+        );
+        assert_eq!(trace.holdings_after(21), None);
+        assert_eq!(trace.holdings_after(0), None);
+    }
+
+    #[test]
+    fn test_inspections_match_puzzle() {
+        let (_, trace) = traced_monkey_game(EXAMPLE, 1000, true).unwrap();
+        assert_eq!(
+            trace.inspections_after(20).unwrap(),
+            "Monkey 0 inspected items 99 times.
+Monkey 1 inspected items 97 times.
+Monkey 2 inspected items 8 times.
+Monkey 3 inspected items 103 times.
+"
+        );
+        let csv = trace.inspections_csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("round,monkey 0,monkey 1,monkey 2,monkey 3")
+        );
+        assert_eq!(lines.next(), Some("1,2,4,3,6"));
+        let totals = csv.lines().skip(1).fold(vec![0; 4], |mut acc, line| {
+            for (total, count) in acc.iter_mut().zip(line.split(',').skip(1)) {
+                *total += count.parse::<usize>().unwrap();
+            }
+            acc
+        });
+        assert_eq!(totals, vec![5204, 4792, 199, 5192]);
+    }
+
+    #[test]
+    fn test_item_paths() {
+        let (_, trace) = traced_monkey_game(EXAMPLE, 1, false).unwrap();
+        // The first item starts at monkey 0 with worry 79, is thrown to monkey 3 at 500
+        // and then on to monkey 1 at 167 in the same round
+        assert_eq!(
+            trace.paths[0],
+            vec![
+                Throw {
+                    round: 1,
+                    from: 0,
+                    to: 3,
+                    worry: 500
+                },
+                Throw {
+                    round: 1,
+                    from: 3,
+                    to: 1,
+                    worry: 167
+                },
+            ]
+        );
+        assert!(trace
+            .item_report()
+            .starts_with("item 0: 0 (79) -> 3 (500) in round 1"));
+    }
+
+    #[test]
+    fn test_fast_matches_simulation() {
+        assert_eq!(fast_monkey_game(EXAMPLE, 20, false).unwrap(), 10605);
+        assert_eq!(fast_monkey_game(EXAMPLE, 10000, true).unwrap(), 2713310158);
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let relief = Relief::new(&monkeys, true);
+        assert_eq!(
+            inspections_by_cycles(&monkeys, relief, 1000).unwrap(),
+            vec![5204, 4792, 199, 5192]
+        );
+    }
+
+    #[test]
+    fn test_fast_many_rounds() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let relief = Relief::new(&monkeys, true);
+        let (_, trace) = traced_monkey_game(EXAMPLE, 100_000, true).unwrap();
+        let simulated: Vec<u64> = trace.inspections[99_999]
+            .iter()
+            .map(|&c| c as u64)
+            .collect();
+        let counts = inspections_by_cycles(&monkeys, relief, 100_000).unwrap();
+        assert_eq!(counts, simulated);
+
+        // Inspections grow linearly once every item is in its cycle
+        let many = inspections_by_cycles(&monkeys, relief, 1_000_000_000).unwrap();
+        for (&m, &c) in many.iter().zip(counts.iter()) {
+            let expected = c * 10_000;
+            assert!(m.abs_diff(expected) < expected / 1000);
+        }
+    }
 }
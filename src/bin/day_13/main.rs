@@ -1,16 +1,24 @@
 use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
 use std::io::{stdin, Read};
 use std::iter::Peekable;
-use std::str::Chars;
+use std::str::{Chars, FromStr};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
+use serde_json::Value;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 enum Packets {
     Integer(i32),
     List(Vec<Packets>),
 }
 
+impl Packets {
+    fn is_list(&self) -> bool {
+        matches!(self, Packets::List(_))
+    }
+}
+
 impl PartialOrd for Packets {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -19,83 +27,391 @@ impl PartialOrd for Packets {
 
 impl Ord for Packets {
     fn cmp(&self, other: &Self) -> Ordering {
-        use std::cmp::Ordering::{Equal, Greater, Less};
-        use Packets::{Integer, List};
+        compare(self, other, None, 0)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Why a comparison stopped descending
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Verdict {
+    LeftSmaller,
+    RightSmaller,
+    LeftRanOut,
+    RightRanOut,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Event {
+    Compare(Packets, Packets),
+    /// An integer on `side` was wrapped into the list `to` to compare against a list
+    Promote {
+        side: Side,
+        to: Packets,
+    },
+    Decided(Verdict),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Step {
+    depth: usize,
+    event: Event,
+}
+
+/// Every step taken while comparing two packets, printed like the puzzle's walkthrough
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+struct Trace {
+    steps: Vec<Step>,
+}
+
+impl Trace {
+    fn record(&mut self, depth: usize, event: Event) {
+        self.steps.push(Step { depth, event });
+    }
+
+    /// The verdict that ended the comparison, `None` if the packets were equal
+    fn verdict(&self) -> Option<Verdict> {
+        self.steps.iter().find_map(|step| match step.event {
+            Event::Decided(verdict) => Some(verdict),
+            _ => None,
+        })
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Integer(left) => match other {
-                Integer(right) => left.cmp(right),
-                List(_) => List(vec![Integer(*left)]).cmp(other),
-            },
-            List(left) => match other {
-                Integer(right) => self.cmp(&List(vec![Integer(*right)])),
-                List(right) => left
-                    .iter()
-                    .zip(right)
-                    .find_map(|(left, right)| match left.cmp(right) {
-                        Less => Some(Less),
-                        Equal => None,
-                        Greater => Some(Greater),
-                    })
-                    .unwrap_or_else(|| left.len().cmp(&right.len())),
-            },
+            Event::Compare(left, right) => write!(f, "Compare {left} vs {right}"),
+            Event::Promote { side, to } => {
+                let side = match side {
+                    Side::Left => "left",
+                    Side::Right => "right",
+                };
+                write!(
+                    f,
+                    "Mixed types; convert {side} to {to} and retry comparison"
+                )
+            }
+            Event::Decided(verdict) => f.write_str(match verdict {
+                Verdict::LeftSmaller => "Left side is smaller, so input is in the right order",
+                Verdict::RightSmaller => {
+                    "Right side is smaller, so input is not in the right order"
+                }
+                Verdict::LeftRanOut => "Left side ran out of items, so input is in the right order",
+                Verdict::RightRanOut => {
+                    "Right side ran out of items, so input is not in the right order"
+                }
+            }),
         }
     }
 }
 
-fn list_from_line(line: &str) -> Result<Packets> {
-    parse_item(&mut line.chars().peekable())
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{:indent$}- {}", "", step.event, indent = 2 * step.depth)?;
+        }
+        Ok(())
+    }
 }
 
-fn parse_item(chars: &mut Peekable<Chars>) -> Result<Packets> {
-    match chars.next() {
-        Some('[') => parse_list(chars),
-        Some(c) => {
-            let mut digits: String = c.into();
-            while let Some(digit) = chars.next_if(|&ch| ch.is_ascii_digit()) {
-                digits.push(digit);
+/// Record the event built by `event` if a trace is being kept
+fn note(trace: &mut Option<&mut Trace>, depth: usize, event: impl FnOnce() -> Event) {
+    if let Some(trace) = trace {
+        trace.record(depth, event());
+    }
+}
+
+/// Compare `left` against `right`, recording each step into `trace` if given
+fn compare(
+    left: &Packets,
+    right: &Packets,
+    mut trace: Option<&mut Trace>,
+    depth: usize,
+) -> Ordering {
+    use Packets::{Integer, List};
+    note(&mut trace, depth, || {
+        Event::Compare(left.clone(), right.clone())
+    });
+    let (ordering, verdicts) = match (left, right) {
+        (Integer(l), Integer(r)) => (l.cmp(r), [Verdict::LeftSmaller, Verdict::RightSmaller]),
+        (Integer(int), List(_)) | (List(_), Integer(int)) => {
+            let side = if left.is_list() {
+                Side::Right
+            } else {
+                Side::Left
+            };
+            let promoted = List(vec![Integer(*int)]);
+            note(&mut trace, depth + 1, || Event::Promote {
+                side,
+                to: promoted.clone(),
+            });
+            return match side {
+                Side::Left => compare(&promoted, right, trace, depth + 1),
+                Side::Right => compare(left, &promoted, trace, depth + 1),
+            };
+        }
+        (List(l), List(r)) => {
+            for (a, b) in l.iter().zip(r) {
+                let ordering = compare(a, b, trace.as_deref_mut(), depth + 1);
+                if ordering.is_ne() {
+                    return ordering;
+                }
             }
-            let int = digits.parse()?;
-            Ok(Packets::Integer(int))
+            let ordering = l.len().cmp(&r.len());
+            (ordering, [Verdict::LeftRanOut, Verdict::RightRanOut])
         }
-        None => Err(Error::msg("Parse error")),
+    };
+    let [less, greater] = verdicts;
+    match ordering {
+        Ordering::Less => note(&mut trace, depth + 1, || Event::Decided(less)),
+        Ordering::Greater => note(&mut trace, depth + 1, || Event::Decided(greater)),
+        Ordering::Equal => {}
     }
+    ordering
 }
 
-fn parse_list(chars: &mut Peekable<Chars>) -> Result<Packets> {
-    let mut many = Vec::new();
-    loop {
-        if let Some(ch) = chars.next_if(|&ch| ch == ',' || ch == ']') {
-            match ch {
-                ',' => {}
-                ']' => break,
-                _ => {
-                    return Err(Error::msg("Syntax error"));
+/// Compare two packets, returning the steps that led to the result
+fn explain(left: &Packets, right: &Packets) -> (Ordering, Trace) {
+    let mut trace = Trace::default();
+    let ordering = compare(left, right, Some(&mut trace), 0);
+    (ordering, trace)
+}
+
+impl Display for Packets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Packets::Integer(int) => write!(f, "{int}"),
+            Packets::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
                 }
+                write!(f, "]")
             }
-        } else {
-            let item = parse_item(chars)?;
-            many.push(item);
         }
     }
-    Ok(Packets::List(many))
+}
+
+impl From<&Packets> for Value {
+    fn from(packet: &Packets) -> Self {
+        match packet {
+            Packets::Integer(int) => Value::from(*int),
+            Packets::List(items) => Value::Array(items.iter().map(Value::from).collect()),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Packets {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Number(number) => number
+                .as_i64()
+                .and_then(|int| i32::try_from(int).ok())
+                .map(Packets::Integer)
+                .with_context(|| format!("{number} is not a packet integer")),
+            Value::Array(items) => Ok(Packets::List(
+                items.iter().map(Packets::try_from).collect::<Result<_>>()?,
+            )),
+            other => Err(anyhow!("{other} can't be part of a packet")),
+        }
+    }
+}
+
+impl FromStr for Packets {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let mut cursor = Cursor {
+            chars: line.chars().peekable(),
+            column: 1,
+        };
+        // Integers only appear inside lists, a packet is always a list
+        let packet = match cursor.peek() {
+            Some('[') => cursor.item()?,
+            Some(ch) => return Err(cursor.error(format!("expected `[`, found `{ch}`"))),
+            None => return Err(cursor.error("expected `[`, found end of line")),
+        };
+        match cursor.peek() {
+            None => Ok(packet),
+            Some(ch) => Err(cursor.error(format!("unexpected `{ch}` after packet"))),
+        }
+    }
+}
+
+/// Walks a packet line one character at a time, keeping track of the 1-based column
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    column: usize,
+}
+
+impl Cursor<'_> {
+    fn error(&self, message: impl Display) -> Error {
+        anyhow!("column {}: {message}", self.column)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        self.column += 1;
+        Some(ch)
+    }
+
+    fn item(&mut self) -> Result<Packets> {
+        match self.peek() {
+            Some('[') => {
+                self.bump();
+                self.list()
+            }
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => self.integer(),
+            Some(ch) => Err(self.error(format!("expected `[` or an integer, found `{ch}`"))),
+            None => Err(self.error("expected `[` or an integer, found end of line")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<Packets> {
+        let column = self.column;
+        let mut digits = String::new();
+        if self.peek() == Some('-') {
+            digits.extend(self.bump());
+        }
+        while let Some(digit) = self.chars.next_if(char::is_ascii_digit) {
+            self.column += 1;
+            digits.push(digit);
+        }
+        digits
+            .parse()
+            .map(Packets::Integer)
+            .map_err(|err| anyhow!("column {column}: bad integer `{digits}`: {err}"))
+    }
+
+    /// The items of a list whose `[` has already been consumed, up to and including the `]`
+    fn list(&mut self) -> Result<Packets> {
+        let mut many = Vec::new();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Packets::List(many));
+        }
+        loop {
+            many.push(self.item()?);
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {
+                    self.bump();
+                    return Ok(Packets::List(many));
+                }
+                Some(ch) => return Err(self.error(format!("expected `,` or `]`, found `{ch}`"))),
+                None => return Err(self.error("expected `,` or `]`, found end of line")),
+            }
+        }
+    }
+}
+
+fn list_from_line(line: &str) -> Result<Packets> {
+    line.parse()
 }
 
 fn line_pairs(lines: &str) -> Result<Vec<(Packets, Packets)>> {
     let mut result = Vec::new();
-    let mut it = lines.lines();
+    let mut it = lines.lines().enumerate();
+    let parse =
+        |(i, line): (usize, &str)| list_from_line(line).with_context(|| format!("line {}", i + 1));
     while let Some(left) = it.next() {
         let right = it.next().context("Need pair of lines")?;
-        let _blank = it.next();
-        let left_p = list_from_line(left)?;
-        let right_p = list_from_line(right)?;
-        result.push((left_p, right_p))
+        if let Some((i, blank)) = it.next() {
+            if !blank.is_empty() {
+                return Err(anyhow!(
+                    "line {}: expected a blank line between pairs",
+                    i + 1
+                ));
+            }
+        }
+        result.push((parse(left)?, parse(right)?))
     }
     Ok(result)
 }
 
+fn main() -> Result<()> {
+    let mut buf = String::new();
+    stdin().read_to_string(&mut buf)?;
+    let pairs = line_pairs(buf.as_str())?;
+
+    if std::env::args().nth(1).as_deref() == Some("explain") {
+        for (index, (left, right)) in pairs.iter().enumerate() {
+            let (_, trace) = explain(left, right);
+            println!("== Pair {} ==\n{trace}", index + 1);
+            if trace.verdict().is_none() {
+                println!("Packets are equal, no order decided\n");
+            }
+        }
+    }
+
+    let mut sum = 0;
+
+    for (index, (left, right)) in pairs.iter().enumerate() {
+        if left < right {
+            sum += index + 1;
+        }
+    }
+
+    println!("Part 1: {}", sum);
+
+    let firsts = pairs.iter().map(|(left, _)| left);
+    let snds = pairs.iter().map(|(_, right)| right);
+    let mut all: Vec<_> = firsts.chain(snds).collect();
+    let d1 = list_from_line("[[2]]")?;
+    let d2 = list_from_line("[[6]]")?;
+    all.push(&d1);
+    all.push(&d2);
+    all.sort();
+    let mut d1_i = 0;
+    let mut d2_i = 0;
+    for (i, p) in all.iter().enumerate() {
+        if *p == &d1 {
+            d1_i = i + 1;
+        } else if *p == &d2 {
+            d2_i = i + 1;
+        }
+    }
+
+    println!("Part 2: {}", d1_i * d2_i);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::list_from_line;
+    use crate::{explain, line_pairs, list_from_line, Packets, Verdict};
+    use proptest::prelude::*;
+    use serde_json::Value;
+
+    fn packet() -> impl Strategy<Value = Packets> {
+        let item = any::<i32>()
+            .prop_map(Packets::Integer)
+            .prop_recursive(4, 64, 6, |inner| {
+                prop::collection::vec(inner, 0..6).prop_map(Packets::List)
+            });
+        prop::collection::vec(item, 0..6).prop_map(Packets::List)
+    }
+
+    fn error_of(line: &str) -> String {
+        list_from_line(line).unwrap_err().to_string()
+    }
 
     #[test]
     fn test_example_1() {
@@ -152,41 +468,113 @@ mod tests {
         let right = list_from_line("[1,[2,[3,[4,[5,6,0]]]],8,9]").unwrap();
         assert!(left > right);
     }
-}
 
-fn main() -> Result<()> {
-    let mut buf = String::new();
-    stdin().read_to_string(&mut buf)?;
-    let pairs = line_pairs(buf.as_str())?;
-    let mut sum = 0;
+    #[test]
+    fn test_display_canonical() {
+        let packet = list_from_line("[1,[2,[3,[4,[5,6,7]]]],8,9]").unwrap();
+        assert_eq!(packet.to_string(), "[1,[2,[3,[4,[5,6,7]]]],8,9]");
+        assert_eq!(
+            list_from_line("[[],[[]]]").unwrap().to_string(),
+            "[[],[[]]]"
+        );
+    }
 
-    for (index, (left, right)) in pairs.iter().enumerate() {
-        if left < right {
-            sum += index + 1;
-        }
+    #[test]
+    fn test_json_interop() {
+        let value: Value = serde_json::from_str("[[1],[2,3,4]]").unwrap();
+        let packet = Packets::try_from(&value).unwrap();
+        assert_eq!(packet, list_from_line("[[1],[2,3,4]]").unwrap());
+        assert_eq!(Value::from(&packet), value);
+        let bad: Value = serde_json::from_str("[1,\"two\"]").unwrap();
+        assert!(Packets::try_from(&bad).is_err());
+        let big: Value = serde_json::from_str("[1,5000000000]").unwrap();
+        assert!(Packets::try_from(&big).is_err());
     }
 
-    println!("Part 1: {}", sum);
+    #[test]
+    fn test_error_columns() {
+        assert_eq!(error_of(""), "column 1: expected `[`, found end of line");
+        assert_eq!(error_of("5"), "column 1: expected `[`, found `5`");
+        assert_eq!(
+            error_of("[1,2"),
+            "column 5: expected `,` or `]`, found end of line"
+        );
+        assert_eq!(
+            error_of("[1,,2]"),
+            "column 4: expected `[` or an integer, found `,`"
+        );
+        assert_eq!(
+            error_of("[1 2]"),
+            "column 3: expected `,` or `]`, found ` `"
+        );
+        assert_eq!(error_of("[1,2]]"), "column 6: unexpected `]` after packet");
+        assert_eq!(
+            error_of("[1,2,]"),
+            "column 6: expected `[` or an integer, found `]`"
+        );
+        assert!(error_of("[99999999999]").starts_with("column 2: bad integer `99999999999`"));
+        let err = line_pairs("[1]\n[2]\n\n[3]\n[4,x]\n").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 5: column 4: expected `[` or an integer, found `x`"
+        );
+    }
 
-    let firsts = pairs.iter().map(|(left, _)| left);
-    let snds = pairs.iter().map(|(_, right)| right);
-    let mut all: Vec<_> = firsts.chain(snds).collect();
-    let d1 = list_from_line("[[2]]")?;
-    let d2 = list_from_line("[[6]]")?;
-    all.push(&d1);
-    all.push(&d2);
-    all.sort();
-    let mut d1_i = 0;
-    let mut d2_i = 0;
-    for (i, p) in all.iter().enumerate() {
-        if *p == &d1 {
-            d1_i = i + 1;
-        } else if *p == &d2 {
-            d2_i = i + 1;
+    proptest! {
+        #[test]
+        fn prop_display_round_trip(p in packet()) {
+            prop_assert_eq!(list_from_line(&p.to_string()).unwrap(), p);
+        }
+
+        #[test]
+        fn prop_json_round_trip(p in packet()) {
+            let value = Value::from(&p);
+            prop_assert_eq!(value.to_string(), p.to_string());
+            prop_assert_eq!(Packets::try_from(&value).unwrap(), p);
         }
     }
 
-    println!("Part 2: {}", d1_i * d2_i);
+    #[test]
+    fn test_explain_example() {
+        let left = list_from_line("[[1],[2,3,4]]").unwrap();
+        let right = list_from_line("[[1],4]").unwrap();
+        let (ordering, trace) = explain(&left, &right);
+        assert!(ordering.is_lt());
+        assert_eq!(trace.verdict(), Some(Verdict::LeftSmaller));
+        assert_eq!(
+            trace.to_string(),
+            "\
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so input is in the right order
+"
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn test_explain_ran_out() {
+        let left = list_from_line("[7,7,7,7]").unwrap();
+        let right = list_from_line("[7,7,7]").unwrap();
+        let (ordering, trace) = explain(&left, &right);
+        assert!(ordering.is_gt());
+        assert_eq!(trace.verdict(), Some(Verdict::RightRanOut));
+        assert_eq!(trace.steps.last().unwrap().depth, 1);
+        let (ordering, trace) = explain(&left, &left);
+        assert!(ordering.is_eq());
+        assert_eq!(trace.verdict(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_explain_agrees_with_ord(a in packet(), b in packet()) {
+            let (ordering, trace) = explain(&a, &b);
+            prop_assert_eq!(ordering, a.cmp(&b));
+            prop_assert_eq!(trace.verdict().is_none(), ordering.is_eq());
+        }
+    }
 }
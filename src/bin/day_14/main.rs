@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::io::{stdin, Read};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 
 #[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
 struct Vertex {
@@ -29,15 +29,12 @@ pub enum Tile {
     Air,
     Rock,
     Sand,
+    Water,
 }
 
 type Map = HashMap<Vertex, Tile>;
 
-fn new_map() -> Map {
-    let mut out = Map::new();
-    out.insert(Vertex { x: 500, y: 0 }, Tile::Air);
-    out
-}
+const SOURCE: Vertex = Vertex { x: 500, y: 0 };
 
 fn fill_map(map: &mut Map, from: &Vertex, to: &Vertex, tile: Tile) {
     use std::cmp::Ordering::{Equal, Greater, Less};
@@ -61,11 +58,11 @@ fn fill_map(map: &mut Map, from: &Vertex, to: &Vertex, tile: Tile) {
     map.insert(Vertex { x: xy.0, y: xy.1 }, tile);
 }
 
-fn bounds_of(map: &Map) -> Result<((i32, i32), (i32, i32))> {
+fn bounds_of<'a>(vertices: impl Iterator<Item = &'a Vertex>) -> Result<((i32, i32), (i32, i32))> {
     let mut xbounds = None;
     let mut ybounds = None;
 
-    for &vertex in map.keys() {
+    for &vertex in vertices {
         match xbounds {
             None => xbounds = Some((vertex.x, vertex.x)),
             Some((xmin, xmax)) if vertex.x < xmin => {
@@ -102,10 +99,6 @@ fn fill_wall(map: &mut Map, wall: &Wall) {
     }
 }
 
-fn out_of_bounds(vtx: &Vertex, bounds: &((i32, i32), (i32, i32))) -> bool {
-    let ((xmin, xmax), (ymin, ymax)) = bounds;
-    vtx.x < *xmin || vtx.x > *xmax || vtx.y > *ymax || vtx.y < *ymin
-}
 #[derive(Debug, PartialEq, Eq)]
 enum Placed {
     Void,
@@ -113,96 +106,331 @@ enum Placed {
     Location(Vertex),
 }
 
-fn occupied(map: &Map, vtx: &Vertex) -> bool {
-    map.get(vtx).unwrap_or(&Tile::Air) != &Tile::Air
+/// The scanned rock along with everything that has come to rest in it so far.
+///
+/// Without a floor, anything falling below the deepest rock is lost to the void. Walls are
+/// solid columns at the given x coordinates, reaching down to any depth.
+struct Cave {
+    map: Map,
+    sources: Vec<Vertex>,
+    floor: Option<i32>,
+    walls: Option<(i32, i32)>,
+    /// Depth of the lowest rock
+    lowest: i32,
+    /// Horizontal extent of everything in the cave, water flowing past it runs off
+    extent: (i32, i32),
 }
 
-fn sandfall(map: &Map, bounds: &((i32, i32), (i32, i32)), origin: &Vertex) -> Placed {
-    let down = Vertex {
-        x: origin.x,
-        y: origin.y + 1,
-    };
-    let down_right = Vertex {
-        x: origin.x + 1,
-        y: origin.y + 1,
-    };
-    let down_left = Vertex {
-        x: origin.x - 1,
-        y: origin.y + 1,
-    };
-    // We placed it in the void
-    let result = if out_of_bounds(origin, bounds) {
-        Placed::Void
-    } else if occupied(map, &down) {
-        if occupied(map, &down_left) {
-            if occupied(map, &down_right) {
-                Placed::Occupied
-            } else {
-                sandfall(map, bounds, &down_right)
+impl Cave {
+    fn new(walls: &[Wall]) -> Result<Cave> {
+        let mut map = Map::new();
+        for wall in walls {
+            fill_wall(&mut map, wall);
+        }
+        let (extent, (_, lowest)) = bounds_of(map.keys())?;
+        let mut cave = Cave {
+            map,
+            sources: Vec::new(),
+            floor: None,
+            walls: None,
+            lowest,
+            extent,
+        };
+        cave.with_sources(vec![SOURCE]);
+        Ok(cave)
+    }
+
+    fn with_sources(&mut self, sources: Vec<Vertex>) -> &mut Self {
+        for source in &sources {
+            self.widen(source.x);
+        }
+        self.sources = sources;
+        self
+    }
+
+    /// Add an endless floor `offset` below the lowest rock
+    fn with_floor(&mut self, offset: i32) -> &mut Self {
+        self.floor = Some(self.lowest + offset);
+        self
+    }
+
+    fn with_walls(&mut self, left: i32, right: i32) -> &mut Self {
+        self.walls = Some((left, right));
+        self
+    }
+
+    fn widen(&mut self, x: i32) {
+        self.extent = (self.extent.0.min(x), self.extent.1.max(x));
+    }
+
+    fn tile(&self, vtx: &Vertex) -> Tile {
+        if self.floor.is_some_and(|floor| vtx.y >= floor)
+            || self
+                .walls
+                .is_some_and(|(left, right)| vtx.x <= left || vtx.x >= right)
+        {
+            return Tile::Rock;
+        }
+        *self.map.get(vtx).unwrap_or(&Tile::Air)
+    }
+
+    fn occupied(&self, vtx: &Vertex) -> bool {
+        self.tile(vtx) != Tile::Air
+    }
+
+    /// Follow one unit of `material` poured at `source` until it comes to rest.
+    ///
+    /// Both materials fall straight down, then diagonally left, then diagonally right. Where
+    /// sand then stops, water flows sideways, trying left before right and keeping that
+    /// direction until it can fall again or hits something.
+    fn drop(&self, source: Vertex, material: Tile) -> Placed {
+        if self.occupied(&source) {
+            return Placed::Occupied;
+        }
+        let mut at = source;
+        let mut flowing = None;
+        loop {
+            if self.floor.is_none() && at.y > self.lowest {
+                return Placed::Void;
+            }
+            let below = [0, -1, 1].map(|dx| Vertex {
+                x: at.x + dx,
+                y: at.y + 1,
+            });
+            if let Some(&next) = below.iter().find(|vtx| !self.occupied(vtx)) {
+                at = next;
+                flowing = None;
+                continue;
+            }
+            if material != Tile::Water {
+                return Placed::Location(at);
+            }
+            let directions = match flowing {
+                None => vec![-1, 1],
+                Some(dx) => vec![dx],
+            };
+            let side = directions
+                .into_iter()
+                .map(|dx| (dx, Vertex { x: at.x + dx, ..at }))
+                .find(|(_, vtx)| !self.occupied(vtx));
+            match side {
+                None => return Placed::Location(at),
+                Some((dx, next)) => {
+                    let (xmin, xmax) = self.walls.unwrap_or(self.extent);
+                    if next.x < xmin || next.x > xmax {
+                        return Placed::Void;
+                    }
+                    at = next;
+                    flowing = Some(dx);
+                }
             }
-        } else {
-            sandfall(map, bounds, &down_left)
         }
-    } else {
-        sandfall(map, bounds, &down)
-    };
+    }
+
+    fn place(&mut self, vtx: Vertex, material: Tile) {
+        self.map.insert(vtx, material);
+        self.widen(vtx.x);
+    }
+
+    /// Pour `material` from every source in turn until none of them can place any more,
+    /// returning how many units came to rest.
+    fn fill(&mut self, material: Tile) -> usize {
+        let mut active = self.sources.clone();
+        let mut placed = 0;
+        while !active.is_empty() {
+            let mut still_active = Vec::new();
+            for source in active {
+                if let Placed::Location(vtx) = self.drop(source, material) {
+                    self.place(vtx, material);
+                    placed += 1;
+                    still_active.push(source);
+                }
+            }
+            active = still_active;
+        }
+        placed
+    }
+
+    /// How much sand comes to rest before the only source is blocked, without simulating.
+    ///
+    /// With a floor, sand ends up in exactly the cells it can reach from the source, and a cell
+    /// is reachable if it is free and one of the three cells above it is reachable. That makes
+    /// one pass over the triangle under the source enough, row by row.
+    fn settled_under_source(&self) -> Result<usize> {
+        let [source] = self.sources[..] else {
+            return Err(anyhow!(
+                "Need exactly one source, found {}",
+                self.sources.len()
+            ));
+        };
+        let floor = self.floor.context("Need a floor to bound the pile")?;
+        if self.occupied(&source) {
+            return Ok(0);
+        }
+        // Row `dy` below the source spans source.x - dy ..= source.x + dy
+        let mut reachable = vec![true];
+        let mut settled = 1;
+        for dy in 1..floor - source.y {
+            let y = source.y + dy;
+            let xmin = source.x - dy;
+            let row: Vec<bool> = (0..reachable.len() + 2)
+                .map(|i| {
+                    // Index i in the row above is one column to the right of index i here
+                    (i.saturating_sub(2)..=i).any(|j| reachable.get(j) == Some(&true))
+                        && !self.occupied(&Vertex {
+                            x: xmin + i as i32,
+                            y,
+                        })
+                })
+                .collect();
+            let count = row.iter().filter(|&&free| free).count();
+            if count == 0 {
+                break;
+            }
+            settled += count;
+            reachable = row;
+        }
+        Ok(settled)
+    }
 
-    match result {
-        Placed::Void => Placed::Void,
-        Placed::Occupied if !occupied(map, origin) => Placed::Location(*origin),
-        r => r,
+    /// Draw the cave like the puzzle does, with `~` for water
+    fn render(&self) -> String {
+        let mut vertices: Vec<Vertex> = self.map.keys().chain(&self.sources).copied().collect();
+        if let Some((left, right)) = self.walls {
+            vertices.push(Vertex { x: left, y: 0 });
+            vertices.push(Vertex { x: right, y: 0 });
+        }
+        if let Some(floor) = self.floor {
+            vertices.push(Vertex { y: floor, ..SOURCE });
+        }
+        let Ok(((mut xmin, mut xmax), (ymin, ymax))) = bounds_of(vertices.iter()) else {
+            return String::new();
+        };
+        // Nothing beyond the walls is interesting
+        if let Some((left, right)) = self.walls {
+            (xmin, xmax) = (left, right);
+        }
+        let mut out = String::new();
+        for y in ymin..=ymax {
+            for x in xmin..=xmax {
+                let vtx = Vertex { x, y };
+                out.push(match self.tile(&vtx) {
+                    Tile::Air if self.sources.contains(&vtx) => '+',
+                    Tile::Air => '.',
+                    Tile::Rock => '#',
+                    Tile::Sand => 'o',
+                    Tile::Water => '~',
+                });
+            }
+            out.push('\n');
+        }
+        out
     }
 }
 
-fn sandfall_p2(map: &Map, bounds: &(i32, i32), origin: &Vertex) -> Placed {
-    let down = Vertex {
-        x: origin.x,
-        y: origin.y + 1,
-    };
-    let down_right = Vertex {
-        x: origin.x + 1,
-        y: origin.y + 1,
-    };
-    let down_left = Vertex {
-        x: origin.x - 1,
-        y: origin.y + 1,
-    };
+/// Options for a custom run, from `key=value` arguments
+struct Options {
+    sources: Vec<Vertex>,
+    floor: Option<i32>,
+    walls: Option<(i32, i32)>,
+    material: Tile,
+    render: bool,
+}
 
-    let result = if origin.y < bounds.0 || origin.y > bounds.1 {
-        Placed::Occupied
-    } else if occupied(map, &down) {
-        if occupied(map, &down_left) {
-            if occupied(map, &down_right) {
-                Placed::Occupied
-            } else {
-                sandfall_p2(map, bounds, &down_right)
+impl Options {
+    fn from_args() -> Result<Options> {
+        let mut options = Options {
+            sources: Vec::new(),
+            floor: None,
+            walls: None,
+            material: Tile::Sand,
+            render: false,
+        };
+        for arg in std::env::args().skip(1) {
+            match arg.split_once('=') {
+                Some(("source", xy)) => {
+                    let (x, y) = xy.split_once(',').context("Expected source=x,y")?;
+                    options.sources.push(Vertex {
+                        x: x.parse()?,
+                        y: y.parse()?,
+                    });
+                }
+                Some(("floor", offset)) => options.floor = Some(offset.parse()?),
+                Some(("walls", xs)) => {
+                    let (left, right) = xs.split_once(',').context("Expected walls=left,right")?;
+                    options.walls = Some((left.parse()?, right.parse()?));
+                }
+                None if arg == "water" => options.material = Tile::Water,
+                None if arg == "render" => options.render = true,
+                _ => return Err(anyhow!("Unknown option {arg}")),
             }
-        } else {
-            sandfall_p2(map, bounds, &down_left)
         }
+        Ok(options)
+    }
+
+    fn is_custom(&self) -> bool {
+        !self.sources.is_empty()
+            || self.floor.is_some()
+            || self.walls.is_some()
+            || self.material != Tile::Sand
+    }
+}
+
+fn main() -> Result<()> {
+    let options = Options::from_args()?;
+    let mut inp = String::new();
+    stdin().read_to_string(&mut inp)?;
+    let walls = inp
+        .lines()
+        .map(parse_vertices)
+        .collect::<Result<Vec<_>>>()?;
+
+    if options.is_custom() {
+        let mut cave = Cave::new(&walls)?;
+        if !options.sources.is_empty() {
+            cave.with_sources(options.sources.clone());
+        }
+        if let Some(offset) = options.floor {
+            cave.with_floor(offset);
+        }
+        if let Some((left, right)) = options.walls {
+            cave.with_walls(left, right);
+        }
+        let placed = cave.fill(options.material);
+        if options.render {
+            println!("{}", cave.render());
+        }
+        println!("Placed {} tiles", placed);
+        return Ok(());
+    }
+
+    let mut cave = Cave::new(&walls)?;
+    let placed = cave.fill(Tile::Sand);
+    if options.render {
+        println!("{}", cave.render());
+    }
+    println!("Placed {} tiles of sand", placed);
+
+    let mut cave = Cave::new(&walls)?;
+    cave.with_floor(2);
+    let placed = if options.render {
+        let placed = cave.fill(Tile::Sand);
+        println!("{}", cave.render());
+        placed
     } else {
-        sandfall_p2(map, bounds, &down)
+        cave.settled_under_source()?
     };
+    println!("Placed {} tiles of sand", placed);
 
-    match result {
-        Placed::Void if !occupied(map, origin) => Placed::Location(*origin),
-        Placed::Occupied if !occupied(map, origin) => Placed::Location(*origin),
-        r => r,
-    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::bounds_of;
-    use crate::fill_wall;
-    use crate::new_map;
-    use crate::sandfall;
-    use crate::sandfall_p2;
-    use crate::Placed::Location;
-    use crate::Tile;
-    use crate::Vertex;
-    use crate::{parse_vertices, Wall};
+    use crate::Placed::{Location, Occupied, Void};
+    use crate::{bounds_of, parse_vertices, Cave, Tile, Vertex, Wall, SOURCE};
     use anyhow::Result;
+    use proptest::prelude::*;
 
     fn parse_walls(inp: &str) -> Result<Vec<Wall>> {
         inp.lines().map(parse_vertices).collect()
@@ -238,14 +466,9 @@ mod tests {
 
     #[test]
     fn test_example_bounds() {
-        let mut map = new_map();
-        for line in EXAMPLE.lines() {
-            if !line.is_empty() {
-                let wall = parse_vertices(line).unwrap();
-                fill_wall(&mut map, &wall);
-            }
-        }
-        let ((xmin, xmax), (ymin, ymax)) = bounds_of(&map).unwrap();
+        let cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        let vertices = cave.map.keys().chain(&cave.sources);
+        let ((xmin, xmax), (ymin, ymax)) = bounds_of(vertices).unwrap();
         assert_eq!(xmin, 494);
         assert_eq!(xmax, 503);
         assert_eq!(ymin, 0);
@@ -254,89 +477,146 @@ mod tests {
 
     #[test]
     fn test_example_sandfall() {
-        let mut map = new_map();
-        let walls = parse_walls(EXAMPLE).unwrap();
-        for wall in walls {
-            fill_wall(&mut map, &wall);
-        }
-        let bounds = bounds_of(&map).unwrap();
-        let first_sandfall = sandfall(&map, &bounds, &Vertex { x: 500, y: 0 });
+        let mut cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        let first_sandfall = cave.drop(SOURCE, Tile::Sand);
         assert_eq!(first_sandfall, Location(Vertex { x: 500, y: 8 }));
         match first_sandfall {
-            Location(sand) => {
-                map.insert(sand, Tile::Sand);
-            }
+            Location(sand) => cave.place(sand, Tile::Sand),
             _ => panic!("Should place first sand"),
         }
-        let second_sandfall = sandfall(&map, &bounds, &Vertex { x: 500, y: 0 });
+        let second_sandfall = cave.drop(SOURCE, Tile::Sand);
         assert_eq!(second_sandfall, Location(Vertex { x: 499, y: 8 }));
     }
 
     #[test]
     fn place_example_sand() {
-        let mut map = new_map();
-        let walls = parse_walls(EXAMPLE).unwrap();
-        for wall in walls {
-            fill_wall(&mut map, &wall);
-        }
-        let bounds = bounds_of(&map).unwrap();
-        let mut placed = 0;
-        while let Location(sand) = sandfall(&map, &bounds, &Vertex { x: 500, y: 0 }) {
-            map.insert(sand, Tile::Sand);
-            placed += 1;
-        }
-        assert_eq!(placed, 24);
+        let mut cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        assert_eq!(cave.fill(Tile::Sand), 24);
+        assert_eq!(
+            cave.render(),
+            "\
+......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.
+"
+        );
     }
 
     #[test]
     fn place_example_sand_no_void() {
-        let mut map = new_map();
-        let walls = parse_walls(EXAMPLE).unwrap();
-        for wall in walls {
-            fill_wall(&mut map, &wall);
-        }
-        let ((xmin, xmax), (ymin, ymax)) = bounds_of(&map).unwrap();
-        let bounds = ((xmin, xmax), (ymin, ymax));
-        let mut placed = 0;
-        while let Location(sand) = sandfall_p2(&map, &bounds.1, &Vertex { x: 500, y: 0 }) {
-            map.insert(sand, Tile::Sand);
-            placed += 1;
-        }
-        assert_eq!(placed, 93);
+        let mut cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        cave.with_floor(2);
+        assert_eq!(cave.fill(Tile::Sand), 93);
+        assert_eq!(cave.drop(SOURCE, Tile::Sand), Occupied);
     }
-}
 
-fn main() -> Result<()> {
-    let mut map = new_map();
-    let mut inp = String::new();
-    stdin().read_to_string(&mut inp)?;
-    for line in inp.lines() {
-        let wall = parse_vertices(line)?;
-        fill_wall(&mut map, &wall);
+    #[test]
+    fn test_separate_sources() {
+        // A second pile far enough away to never touch the first one, on a bare floor at y = 11
+        let mut cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        cave.with_sources(vec![SOURCE, Vertex { x: 600, y: 0 }])
+            .with_floor(2);
+        assert_eq!(cave.fill(Tile::Sand), 93 + 11 * 11);
     }
-    let bounds = bounds_of(&map)?;
 
-    let mut placed = 0;
-    while let Placed::Location(sand) = sandfall(&map, &bounds, &Vertex { x: 500, y: 0 }) {
-        map.insert(sand, Tile::Sand);
-        placed += 1;
+    #[test]
+    fn test_walls() {
+        let mut cave = Cave::new(&parse_walls(EXAMPLE).unwrap()).unwrap();
+        cave.with_walls(497, 503).with_floor(2);
+        let placed = cave.fill(Tile::Sand);
+        assert_eq!(placed, 31);
+        assert_eq!(
+            cave.render(),
+            "\
+#..o..#
+#.ooo.#
+#ooooo#
+#ooooo#
+##ooo##
+##ooo##
+##ooo##
+#oooo##
+#oooo##
+#######
+#.....#
+#######
+"
+        );
     }
 
-    println!("Placed {} tiles of sand", placed);
+    #[test]
+    fn test_water_levels_out() {
+        let cup = parse_walls("10,2 -> 10,5 -> 14,5 -> 14,2").unwrap();
+        let mut water = Cave::new(&cup).unwrap();
+        water.with_sources(vec![Vertex { x: 12, y: 0 }]);
+        assert_eq!(water.fill(Tile::Water), 9);
+        assert_eq!(
+            water.render(),
+            "\
+..+..
+.....
+#~~~#
+#~~~#
+#~~~#
+#####
+"
+        );
+        let mut sand = Cave::new(&cup).unwrap();
+        sand.with_sources(vec![Vertex { x: 12, y: 0 }]);
+        assert_eq!(sand.fill(Tile::Sand), 13);
+    }
 
-    let mut map = new_map();
-    for line in inp.lines() {
-        let wall = parse_vertices(line)?;
-        fill_wall(&mut map, &wall);
+    #[test]
+    fn test_water_runs_off_floor() {
+        let mut cave = Cave::new(&parse_walls("0,3 -> 2,3").unwrap()).unwrap();
+        cave.with_sources(vec![Vertex { x: 1, y: 0 }]).with_floor(2);
+        assert_eq!(cave.drop(Vertex { x: 1, y: 0 }, Tile::Water), Void);
+        assert_eq!(cave.fill(Tile::Water), 0);
+        cave.with_walls(-3, 4);
+        // Walled in, the water fills the whole box around the rock
+        assert_eq!(cave.fill(Tile::Water), 6 * 5 - 3);
     }
-    let bounds = bounds_of(&map)?;
-    let mut placed = 0;
-    while let Placed::Location(sand) = sandfall_p2(&map, &bounds.1, &Vertex { x: 500, y: 0 }) {
-        map.insert(sand, Tile::Sand);
-        placed += 1;
+
+    #[test]
+    fn test_settled_under_source() {
+        let walls = parse_walls(EXAMPLE).unwrap();
+        let mut cave = Cave::new(&walls).unwrap();
+        assert!(cave.settled_under_source().is_err());
+        cave.with_floor(2);
+        assert_eq!(cave.settled_under_source().unwrap(), 93);
+        cave.with_walls(497, 503);
+        assert_eq!(cave.settled_under_source().unwrap(), 31);
+        cave.with_sources(vec![SOURCE, SOURCE]);
+        assert!(cave.settled_under_source().is_err());
     }
 
-    println!("Placed {} tiles of sand", placed);
+    fn segments() -> impl Strategy<Value = Vec<Wall>> {
+        let segment =
+            (490i32..510, 2i32..15, 0i32..6, any::<bool>()).prop_map(|(x, y, len, flat)| {
+                let end = if flat {
+                    Vertex { x: x + len, y }
+                } else {
+                    Vertex { x, y: y + len }
+                };
+                vec![Vertex { x, y }, end]
+            });
+        prop::collection::vec(segment, 1..12)
+    }
 
-    Ok(())
+    proptest! {
+        #[test]
+        fn prop_settled_matches_simulation(walls in segments(), offset in 1i32..4) {
+            let mut cave = Cave::new(&walls).unwrap();
+            cave.with_floor(offset);
+            let fast = cave.settled_under_source().unwrap();
+            prop_assert_eq!(cave.fill(Tile::Sand), fast);
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use std::cmp::{max, min};
+use aoc::interval::IntervalSet;
 use std::collections::HashSet;
 use std::io;
 use std::io::Read;
@@ -66,38 +66,50 @@ fn parse_lines<T: AsRef<str>>(input: T) -> Result<Map> {
         .collect()
 }
 
+/// The x positions on `row` that are within range of some sensor
+fn row_coverage(inputs: &Map, row: i32) -> IntervalSet<i32> {
+    inputs
+        .iter()
+        .filter_map(|Input(sensor, beacon)| {
+            let distance = manhattan_dist(sensor, beacon);
+            let remaining = distance - (sensor.y() - row).abs();
+            (remaining >= 0).then(|| sensor.x() - remaining..=sensor.x() + remaining)
+        })
+        .collect()
+}
+
 fn solve_problem_one(inputs: &Map, row: i32) -> usize {
-    let mut ranges = vec![];
-    for Input(sensor, beacon) in inputs.iter() {
-        let distance = manhattan_dist(sensor, beacon);
-        let remaining = distance - (sensor.y() - row).abs();
-        let intersect_x = sensor.x();
-
-        if remaining <= 0 {
-            continue;
-        }
-        let x1 = intersect_x - remaining;
-        let x2 = intersect_x + remaining;
-        ranges.push(x1..=x2);
-    }
-    ranges.sort_by_key(|range| *range.start());
-    let mut it = ranges.iter().cloned();
-    let mut curr = it.next().unwrap();
-    let mut range_set = vec![];
-
-    for next in it {
-        if curr.end() + 1 >= *next.start() {
-            curr = RangeInclusive::new(*curr.start(), *next.end());
-        } else {
-            range_set.push(curr);
-            curr = next;
-        }
-    }
-    range_set.push(curr);
-    let total_range = range_set.iter().fold((0, 0), |acc, range| {
-        (min(acc.0, *range.start()), max(acc.1, *range.end()))
-    });
-    (total_range.1 - total_range.0) as usize
+    let coverage = row_coverage(inputs, row);
+    let beacons: HashSet<_> = inputs
+        .iter()
+        .map(|Input(_, beacon)| *beacon)
+        .filter(|beacon| beacon.y() == row && coverage.contains(beacon.x()))
+        .collect();
+    coverage.len() as usize - beacons.len()
+}
+
+/// Every location within the bounds that no sensor can see, found by scanning row by row
+fn find_uncovered(
+    inputs: &Map,
+    xbound: RangeInclusive<i32>,
+    ybound: RangeInclusive<i32>,
+) -> Vec<Location> {
+    ybound
+        .flat_map(|y| {
+            row_coverage(inputs, y)
+                .complement(xbound.clone())
+                .iter()
+                .flatten()
+                .map(move |x| Location(x, y))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn is_covered(inputs: &Map, loc: &Location) -> bool {
+    inputs
+        .iter()
+        .any(|Input(sensor, beacon)| manhattan_dist(sensor, loc) <= manhattan_dist(sensor, beacon))
 }
 
 // Find intersection point of y = -x + b_neg and y = x + b_pos
@@ -109,17 +121,52 @@ fn intersect_lines(b_neg: i32, b_pos: i32) -> (i32, i32) {
     (x, y)
 }
 
-fn find_distress_beacon(map: &Map) -> Option<Location> {
-    let (xmin, xmax) = map.iter().fold((0, 0), |(xmin, xmax), input| match input {
-        Input(Location(x, _), _) if *x < xmin => (*x, xmax),
-        Input(Location(x, _), _) if *x > xmax => (xmin, *x),
-        _ => (xmin, xmax),
-    });
-    let (ymin, ymax) = map.iter().fold((0, 0), |(ymin, ymax), input| match input {
-        Input(Location(_, y), _) if *y < ymin => (*y, ymax),
-        Input(Location(_, y), _) if *y > ymax => (ymin, *y),
-        _ => (ymin, ymax),
-    });
+/// The row to count in part 1, and the search box `0..=bound` in both directions for part 2
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+struct Search {
+    row: i32,
+    bound: i32,
+}
+
+impl Search {
+    const EXAMPLE: Search = Search { row: 10, bound: 20 };
+    const PUZZLE: Search = Search {
+        row: 2000000,
+        bound: 4000000,
+    };
+
+    /// The example has all its sensors close to the origin, real inputs are spread out over millions
+    fn detect(map: &Map) -> Search {
+        let small = map
+            .iter()
+            .all(|Input(sensor, _)| sensor.x().abs() < 1000 && sensor.y().abs() < 1000);
+        if small {
+            Search::EXAMPLE
+        } else {
+            Search::PUZZLE
+        }
+    }
+
+    /// Use `row` and `bound` from the command line if given, otherwise detect them
+    fn from_args(map: &Map) -> Result<Search> {
+        let detected = Search::detect(map);
+        let mut args = std::env::args().skip(1);
+        let row = match args.next() {
+            Some(row) => row.parse().context("Expected row as first argument")?,
+            None => detected.row,
+        };
+        let bound = match args.next() {
+            Some(bound) => bound.parse().context("Expected bound as second argument")?,
+            None => detected.bound,
+        };
+        Ok(Search { row, bound })
+    }
+}
+
+/// Every location in the search box that could hold the distress beacon
+fn find_distress_beacons(map: &Map, search: &Search) -> Vec<Location> {
+    let (xmin, xmax) = (0, search.bound);
+    let (ymin, ymax) = (0, search.bound);
 
     // The distress beacon is in some point that is 1 outside of a sensor range
     // and where there is an intersection between "circles" just outside sensor range
@@ -170,12 +217,18 @@ fn find_distress_beacon(map: &Map) -> Option<Location> {
         intersects.retain(|loc| manhattan_dist(sensor, loc) > dist);
     }
 
-    println!("Found {} after filter", intersects.len());
-
-    if intersects.len() != 1 {
-        None
-    } else {
-        intersects.iter().next().cloned()
+    // The intersections only find a beacon hemmed in between sensor ranges, not one on the
+    // edge of the search box or in a bigger gap, so fall back to scanning every row unless
+    // there's exactly one candidate and every neighbour of it inside the box is covered
+    let neighbours = |&Location(x, y): &Location| {
+        [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .map(Location::from)
+            .filter(|loc| (xmin..=xmax).contains(&loc.x()) && (ymin..=ymax).contains(&loc.y()))
+    };
+    match Vec::from_iter(intersects).as_slice() {
+        [candidate] if neighbours(candidate).all(|loc| is_covered(map, &loc)) => vec![*candidate],
+        _ => find_uncovered(map, xmin..=xmax, ymin..=ymax),
     }
 }
 
@@ -185,10 +238,88 @@ fn tuning_distance(loc: &Location) -> i64 {
     x * 4000000 + y
 }
 
+/// Draw sensors, beacons and the area covered by sensors like the puzzle illustrations
+fn render(map: &Map, xbound: RangeInclusive<i32>, ybound: RangeInclusive<i32>) -> String {
+    let sensors: HashSet<_> = map.iter().map(|Input(sensor, _)| *sensor).collect();
+    let beacons: HashSet<_> = map.iter().map(|Input(_, beacon)| *beacon).collect();
+    let label_width = ybound
+        .clone()
+        .map(|y| y.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let column_labels: Vec<_> = xbound
+        .clone()
+        .map(|x| {
+            if x >= 0 && x % 5 == 0 {
+                x.to_string()
+            } else {
+                String::new()
+            }
+        })
+        .collect();
+    let label_height = column_labels.iter().map(|l| l.len()).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for digit in 0..label_height {
+        out.push_str(&" ".repeat(label_width + 1));
+        for label in column_labels.iter() {
+            let skip = label_height - label.len();
+            out.push(if digit < skip {
+                ' '
+            } else {
+                label.as_bytes()[digit - skip] as char
+            });
+        }
+        out.push('\n');
+    }
+    for y in ybound {
+        let coverage = row_coverage(map, y);
+        out.push_str(&format!("{y:>label_width$} "));
+        for x in xbound.clone() {
+            let loc = Location(x, y);
+            out.push(if sensors.contains(&loc) {
+                'S'
+            } else if beacons.contains(&loc) {
+                'B'
+            } else if coverage.contains(x) {
+                '#'
+            } else {
+                '.'
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn main() -> Result<()> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    let map = parse_lines(content)?;
+    let search = Search::from_args(&map)?;
+    let start = Instant::now();
+    let solution = solve_problem_one(&map, search.row);
+    println!("{solution} in {}ms", start.elapsed().as_millis());
+    let start = Instant::now();
+    let candidates = find_distress_beacons(&map, &search);
+    match candidates.as_slice() {
+        [distress_beacon] => {
+            let solution_part_2 = tuning_distance(distress_beacon);
+            println!("{solution_part_2} in {}ms", start.elapsed().as_millis());
+        }
+        _ => println!("Found {} candidates: {candidates:?}", candidates.len()),
+    }
+    if search.bound <= 100 {
+        print!("{}", render(&map, 0..=search.bound, 0..=search.bound));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        find_distress_beacon, manhattan_dist, parse_lines, solve_problem_one, Input, Location,
+        find_distress_beacons, find_uncovered, manhattan_dist, parse_lines, render,
+        solve_problem_one, Input, Location, Search,
     };
 
     const EXAMPLE: &str = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
@@ -235,21 +366,65 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3
     #[test]
     fn test_solve_problem_2_example() {
         let map = parse_lines(EXAMPLE).unwrap();
-        let loc = find_distress_beacon(&map).unwrap();
-        assert_eq!(loc, Location(14, 11));
+        let candidates = find_distress_beacons(&map, &Search::EXAMPLE);
+        assert_eq!(candidates, vec![Location(14, 11)]);
     }
-}
 
-fn main() -> Result<()> {
-    let mut content = String::new();
-    io::stdin().read_to_string(&mut content)?;
-    let map = parse_lines(content)?;
-    let start = Instant::now();
-    let solution = solve_problem_one(&map, 2000000);
-    println!("{solution} in {}ms", start.elapsed().as_millis());
-    let start = Instant::now();
-    let distress_beacon = find_distress_beacon(&map).context("Unable to find 1 point")?;
-    let solution_part_2 = tuning_distance(&distress_beacon);
-    println!("{solution_part_2} in {}ms", start.elapsed().as_millis());
-    Ok(())
+    #[test]
+    fn test_find_uncovered_example() {
+        let map = parse_lines(EXAMPLE).unwrap();
+        assert_eq!(find_uncovered(&map, 0..=20, 0..=20), vec![Location(14, 11)]);
+    }
+
+    #[test]
+    fn test_beacon_on_search_box_edge() {
+        // Sensors covering everything in 0..=4 except the corner (0, 0), which is not
+        // at an intersection of lines just outside the sensor ranges
+        let map = parse_lines(
+            "Sensor at x=4, y=4: closest beacon is at x=4, y=-3
+Sensor at x=0, y=4: closest beacon is at x=0, y=7
+Sensor at x=4, y=0: closest beacon is at x=7, y=0
+",
+        )
+        .unwrap();
+        assert_eq!(find_uncovered(&map, 0..=4, 0..=4), vec![Location(0, 0)]);
+        let search = Search { row: 0, bound: 4 };
+        assert_eq!(find_distress_beacons(&map, &search), vec![Location(0, 0)]);
+    }
+
+    #[test]
+    fn test_sparse_coverage() {
+        // One sensor leaves most of the search box uncovered, including a cell at one of
+        // the intersections just outside its range
+        let map = parse_lines("Sensor at x=10, y=0: closest beacon is at x=10, y=8\n").unwrap();
+        let search = Search { row: 0, bound: 3 };
+        let candidates = find_distress_beacons(&map, &search);
+        assert_eq!(candidates.len(), 13);
+        assert_eq!(candidates, find_uncovered(&map, 0..=3, 0..=3));
+    }
+
+    #[test]
+    fn test_detect_search() {
+        let map = parse_lines(EXAMPLE).unwrap();
+        assert_eq!(Search::detect(&map), Search::EXAMPLE);
+        let map =
+            parse_lines("Sensor at x=2000, y=3000000: closest beacon is at x=-2, y=15\n").unwrap();
+        assert_eq!(Search::detect(&map), Search::PUZZLE);
+    }
+
+    #[test]
+    fn test_render_single_sensor() {
+        // The top of the coverage of the sensor at 8,7 from the puzzle text
+        let map = parse_lines("Sensor at x=8, y=7: closest beacon is at x=2, y=10\n").unwrap();
+        let rendering = render(&map, 0..=10, -3..=0);
+        let expected = [
+            "             1",
+            "   0    5    0",
+            "-3 ...........",
+            "-2 ........#..",
+            "-1 .......###.",
+            " 0 ......#####",
+        ];
+        assert_eq!(rendering.lines().collect::<Vec<_>>(), expected);
+    }
 }
@@ -1,38 +1,23 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use aoc::io::read_stdin;
-use std::collections::{HashSet, VecDeque};
-
-fn face_sides(place: &(i32, i32, i32)) -> Vec<(i32, i32, i32)> {
-    match place {
-        &(x, y, z) => vec![
-            (x + 1, y, z),
-            (x - 1, y, z),
-            (x, y + 1, z),
-            (x, y - 1, z),
-            (x, y, z + 1),
-            (x, y, z - 1),
-        ],
-    }
-}
+use aoc::point3d::{Cuboid, Point3d};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-fn surface_area(droplet: &HashSet<(i32, i32, i32)>) -> usize {
+type Cube = Point3d<i32>;
+
+fn surface_area(droplet: &HashSet<Cube>) -> usize {
     droplet
         .iter()
-        .map(|cube| {
-            face_sides(cube)
-                .iter()
-                .filter(|n| !droplet.contains(n))
-                .count()
-        })
+        .map(|cube| cube.faces().filter(|n| !droplet.contains(n)).count())
         .sum()
 }
 
-fn parse_droplet(inp: &str) -> HashSet<(i32, i32, i32)> {
+fn parse_droplet(inp: &str) -> HashSet<Cube> {
     inp.lines()
         .filter(|line| !line.is_empty())
         .map(|line| {
             let strs: Vec<_> = line.split(',').collect();
-            (
+            Point3d::new(
                 strs[0].parse().unwrap(),
                 strs[1].parse().unwrap(),
                 strs[2].parse().unwrap(),
@@ -41,52 +26,207 @@ fn parse_droplet(inp: &str) -> HashSet<(i32, i32, i32)> {
         .collect()
 }
 
-fn exposed_surface(droplet: &HashSet<(i32, i32, i32)>) -> usize {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-
-    let xmin = droplet.iter().map(|&(x, _, _)| x).min().unwrap() - 1;
-    let xmax = droplet.iter().map(|&(x, _, _)| x).max().unwrap() + 1;
-    let ymin = droplet.iter().map(|&(_, y, _)| y).min().unwrap() - 1;
-    let ymax = droplet.iter().map(|&(_, y, _)| y).max().unwrap() + 1;
-    let zmin = droplet.iter().map(|&(_, _, z)| z).min().unwrap() - 1;
-    let zmax = droplet.iter().map(|&(_, _, z)| z).max().unwrap() + 1;
-
-    // Start somewhere outside the droplet
-    let left = (xmin, ymin, zmin);
-    queue.push_back(left);
-
-    // Use BFS to visit the entire perimeter
-    while !queue.is_empty() {
-        let current = queue.pop_front().unwrap();
-        visited.insert(current);
-
-        for next in face_sides(&current) {
-            if !visited.contains(&next)
-                && !queue.contains(&next)
-                && !droplet.contains(&next)
-                && next.0 >= xmin
-                && next.0 <= xmax
-                && next.1 >= ymin
-                && next.1 <= ymax
-                && next.2 >= zmin
-                && next.2 <= zmax
-            {
-                queue.push_back(next);
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+enum Voxel {
+    Lava,
+    Air,
+    Exterior,
+    Pocket(usize),
+}
+
+/// A pocket of air fully enclosed by lava.
+#[derive(Eq, PartialEq, Debug)]
+struct Pocket {
+    volume: usize,
+    bounds: Cuboid<i32>,
+    surface: usize,
+}
+
+/// A face of a lava cube that touches air, `normal` points out of the lava.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+struct Face {
+    cube: Cube,
+    normal: Cube,
+    exterior: bool,
+}
+
+/// Dense voxel grid covering the droplet with one layer of air around it, so
+/// that all the exterior air is connected.
+struct Volume {
+    bounds: Cuboid<i32>,
+    voxels: Vec<Voxel>,
+    pockets: Vec<Pocket>,
+}
+
+impl Volume {
+    fn new(droplet: &HashSet<Cube>) -> Option<Volume> {
+        let bounds = Cuboid::bound(droplet.iter().copied())?.pad(1);
+        let (w, h, d) = bounds.dims();
+        let mut volume = Volume {
+            bounds,
+            voxels: vec![Voxel::Air; (w * h * d) as usize],
+            pockets: Vec::new(),
+        };
+        for cube in droplet {
+            let idx = volume.index(*cube);
+            volume.voxels[idx] = Voxel::Lava;
+        }
+        volume.fill(bounds.min, Voxel::Exterior);
+
+        for z in bounds.min.z..=bounds.max.z {
+            for y in bounds.min.y..=bounds.max.y {
+                for x in bounds.min.x..=bounds.max.x {
+                    let cube = Point3d::new(x, y, z);
+                    if volume.get(cube) == Some(Voxel::Air) {
+                        let id = volume.pockets.len();
+                        let filled = volume.fill(cube, Voxel::Pocket(id));
+                        let surface = filled
+                            .iter()
+                            .flat_map(|c| c.faces())
+                            .filter(|n| volume.get(*n) == Some(Voxel::Lava))
+                            .count();
+                        volume.pockets.push(Pocket {
+                            volume: filled.len(),
+                            bounds: Cuboid::bound(filled.into_iter()).unwrap(),
+                            surface,
+                        });
+                    }
+                }
             }
         }
+        Some(volume)
     }
 
-    droplet
-        .iter()
-        .map(|cube| {
-            face_sides(cube)
-                .iter()
-                // Count only neighbours that we traversed with BFS
-                .filter(|n| visited.contains(n))
-                .count()
-        })
-        .sum()
+    fn index(&self, cube: Cube) -> usize {
+        let (w, h, _) = self.bounds.dims();
+        let rel = cube - self.bounds.min;
+        ((rel.z * h + rel.y) * w + rel.x) as usize
+    }
+
+    fn get(&self, cube: Cube) -> Option<Voxel> {
+        if self.bounds.contains(cube) {
+            Some(self.voxels[self.index(cube)])
+        } else {
+            None
+        }
+    }
+
+    /// Flood fill the unlabelled air connected to `start` with `label`, returning the cubes that were filled.
+    fn fill(&mut self, start: Cube, label: Voxel) -> Vec<Cube> {
+        let mut filled = Vec::new();
+        let mut queue = VecDeque::new();
+        let idx = self.index(start);
+        self.voxels[idx] = label;
+        queue.push_back(start);
+
+        // Mark when pushing so that every cube enters the queue at most once
+        while let Some(current) = queue.pop_front() {
+            filled.push(current);
+            for next in current.faces() {
+                if self.get(next) == Some(Voxel::Air) {
+                    let idx = self.index(next);
+                    self.voxels[idx] = label;
+                    queue.push_back(next);
+                }
+            }
+        }
+        filled
+    }
+
+    fn faces(&self) -> impl Iterator<Item = Face> + '_ {
+        self.voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, voxel)| **voxel == Voxel::Lava)
+            .flat_map(move |(idx, _)| {
+                let (w, h, _) = self.bounds.dims();
+                let idx = idx as i32;
+                let cube = self.bounds.min + Point3d::new(idx % w, (idx / w) % h, idx / (w * h));
+                cube.faces().filter_map(move |n| match self.get(n)? {
+                    Voxel::Lava => None,
+                    voxel => Some(Face {
+                        cube,
+                        normal: n - cube,
+                        exterior: voxel == Voxel::Exterior,
+                    }),
+                })
+            })
+    }
+
+    fn exterior_surface(&self) -> usize {
+        self.faces().filter(|face| face.exterior).count()
+    }
+
+    fn interior_surface(&self) -> usize {
+        self.faces().filter(|face| !face.exterior).count()
+    }
+
+    /// Wavefront OBJ mesh with one quad per exposed face
+    fn to_obj(&self, exterior_only: bool) -> String {
+        let mut vertices: HashMap<[i32; 3], usize> = HashMap::new();
+        let mut out = String::new();
+        let mut quads = Vec::new();
+        for face in self.faces().filter(|f| f.exterior || !exterior_only) {
+            let quad = face.corners().map(|corner| {
+                let next = vertices.len() + 1;
+                *vertices.entry(corner).or_insert_with(|| {
+                    out.push_str(&format!("v {} {} {}\n", corner[0], corner[1], corner[2]));
+                    next
+                })
+            });
+            quads.push(quad);
+        }
+        for [a, b, c, d] in quads {
+            out.push_str(&format!("f {a} {b} {c} {d}\n"));
+        }
+        out
+    }
+
+    /// ASCII STL mesh with two triangles per exposed face
+    fn to_stl(&self, exterior_only: bool) -> String {
+        let mut out = String::from("solid droplet\n");
+        for face in self.faces().filter(|f| f.exterior || !exterior_only) {
+            let n = face.normal;
+            let [a, b, c, d] = face.corners();
+            for triangle in [[a, b, c], [a, c, d]] {
+                out.push_str(&format!(
+                    "facet normal {} {} {}\n outer loop\n",
+                    n.x, n.y, n.z
+                ));
+                for v in triangle {
+                    out.push_str(&format!("  vertex {} {} {}\n", v[0], v[1], v[2]));
+                }
+                out.push_str(" endloop\nendfacet\n");
+            }
+        }
+        out.push_str("endsolid droplet\n");
+        out
+    }
+}
+
+impl Face {
+    /// Corners of the face, counter-clockwise when seen from outside the lava.
+    fn corners(&self) -> [[i32; 3]; 4] {
+        let cube = [self.cube.x, self.cube.y, self.cube.z];
+        let normal = [self.normal.x, self.normal.y, self.normal.z];
+        let axis = normal.iter().position(|&n| n != 0).unwrap();
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut corners = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(du, dv)| {
+            let mut corner = cube;
+            corner[axis] += i32::from(normal[axis] > 0);
+            corner[u] += du;
+            corner[v] += dv;
+            corner
+        });
+        if normal[axis] < 0 {
+            corners.reverse();
+        }
+        corners
+    }
+}
+
+fn exposed_surface(droplet: &HashSet<Cube>) -> usize {
+    Volume::new(droplet).map_or(0, |volume| volume.exterior_surface())
 }
 
 #[cfg(test)]
@@ -116,14 +256,73 @@ mod tests {
         let droplet = super::parse_droplet(EXAMPLE);
         assert_eq!(super::exposed_surface(&droplet), 58);
     }
+
+    #[test]
+    fn test_pockets_example() {
+        let droplet = super::parse_droplet(EXAMPLE);
+        let volume = super::Volume::new(&droplet).unwrap();
+        assert_eq!(volume.pockets.len(), 1);
+        let pocket = &volume.pockets[0];
+        assert_eq!(pocket.volume, 1);
+        assert_eq!(pocket.bounds.min, (2, 2, 5).into());
+        assert_eq!(pocket.surface, 6);
+        assert_eq!(volume.interior_surface(), 6);
+        assert_eq!(
+            volume.interior_surface() + volume.exterior_surface(),
+            super::surface_area(&droplet)
+        );
+    }
+
+    #[test]
+    fn test_mesh_export() {
+        let droplet = super::parse_droplet("1,1,1\n");
+        let volume = super::Volume::new(&droplet).unwrap();
+        let obj = volume.to_obj(true);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 8);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 6);
+        let stl = volume.to_stl(true);
+        assert_eq!(stl.lines().filter(|l| l.starts_with("facet")).count(), 12);
+        assert!(stl.contains("facet normal 1 0 0"));
+        // Going round the corners turns the same way as the normal points, out of the lava
+        let east = volume.faces().find(|f| f.normal.x == 1).unwrap();
+        assert_eq!(east.corners(), [[2, 1, 1], [2, 2, 1], [2, 2, 2], [2, 1, 2]]);
+        for face in volume.faces() {
+            let [a, b, c, _] = face.corners();
+            let e = [0, 1, 2].map(|i| b[i] - a[i]);
+            let f = [0, 1, 2].map(|i| c[i] - b[i]);
+            let cross = [0, 1, 2].map(|i| {
+                let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+                e[j] * f[k] - e[k] * f[j]
+            });
+            assert_eq!(cross, [face.normal.x, face.normal.y, face.normal.z]);
+        }
+    }
 }
 
 fn main() -> Result<()> {
     let input = read_stdin()?;
     let droplet = parse_droplet(input.as_str());
-    let area = surface_area(&droplet);
-    println!("{area}");
-    let exposed = exposed_surface(&droplet);
-    println!("{exposed}");
+    let volume = Volume::new(&droplet).context("Empty droplet")?;
+
+    match std::env::args().nth(1).as_deref() {
+        Some("obj") => print!("{}", volume.to_obj(false)),
+        Some("stl") => print!("{}", volume.to_stl(false)),
+        Some("pockets") => {
+            println!("interior surface: {}", volume.interior_surface());
+            for pocket in volume.pockets.iter() {
+                println!(
+                    "pocket of {} at {:?}..={:?} with surface {}",
+                    pocket.volume, pocket.bounds.min, pocket.bounds.max, pocket.surface
+                );
+            }
+        }
+        Some(arg) => return Err(anyhow!("Unknown option {arg}")),
+        None => {
+            let area = surface_area(&droplet);
+            println!("{area}");
+            let exposed = exposed_surface(&droplet);
+            println!("{exposed}");
+        }
+    }
     Ok(())
 }
@@ -1,65 +1,123 @@
 use anyhow::{anyhow, Context, Result};
 use aoc::io::read_stdin;
-use regex::Regex;
+use itertools::Itertools;
+use rayon::prelude::*;
 use std::cmp::max;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
 
 type ResourceKind = usize;
 type ResourceAmount = i32;
 
-const RESOURCE_KINDS: ResourceKind = 4;
-const ORE: ResourceKind = 0;
-const CLAY: ResourceKind = 1;
-const OBSIDIAN: ResourceKind = 2;
-const GEODE: ResourceKind = 3;
-const RESOURCE_NAMES: [ResourceKind; RESOURCE_KINDS] = [ORE, CLAY, OBSIDIAN, GEODE];
+/// Amounts of each resource, indexed by kind
+type Resources = Vec<ResourceAmount>;
+type CanBuild = Vec<Option<ResourceAmount>>;
+
+/// Robot recipes for some set of resources.
+///
+/// Resource kinds are numbered in the order their robots are listed, followed by anything that
+/// is only ever spent. The factory starts out with one robot collecting the first kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Blueprint {
+    id: i32,
+    names: Vec<String>,
+    /// What a robot collecting each kind costs, no robot can be built for spend-only kinds
+    costs: Vec<Option<Resources>>,
+}
 
-type Resources = [ResourceAmount; RESOURCE_KINDS];
-type Blueprint = [Resources; RESOURCE_KINDS];
-type CanBuild = [Option<ResourceAmount>; RESOURCE_KINDS];
+impl Blueprint {
+    fn new(id: i32, recipes: Vec<(&str, Vec<(ResourceAmount, &str)>)>) -> Result<Blueprint> {
+        let mut names: Vec<String> = Vec::new();
+        for (robot, _) in &recipes {
+            if names.iter().any(|name| name == robot) {
+                return Err(anyhow!("Blueprint {id}: {robot} robot listed twice"));
+            }
+            names.push(robot.to_string());
+        }
+        if names.is_empty() {
+            return Err(anyhow!("Blueprint {id}: no robots"));
+        }
+        for (_, costs) in &recipes {
+            for &(_, component) in costs {
+                if !names.iter().any(|name| name == component) {
+                    names.push(component.to_string());
+                }
+            }
+        }
+        let mut bp = Blueprint {
+            id,
+            costs: vec![None; names.len()],
+            names,
+        };
+        for (kind, (_, costs)) in recipes.into_iter().enumerate() {
+            let mut price = bp.new_resources();
+            for (amount, component) in costs {
+                let component = bp.kind(component).expect("All components are named");
+                price[component] += amount;
+            }
+            bp.costs[kind] = Some(price);
+        }
+        Ok(bp)
+    }
 
-fn new_resources() -> Resources {
-    [0; RESOURCE_KINDS]
-}
+    fn kinds(&self) -> usize {
+        self.names.len()
+    }
 
-fn new_blueprint() -> Blueprint {
-    [[0; RESOURCE_KINDS]; RESOURCE_KINDS]
-}
+    fn kind(&self, name: &str) -> Option<ResourceKind> {
+        self.names.iter().position(|known| known == name)
+    }
 
-fn new_can_build() -> CanBuild {
-    [None; RESOURCE_KINDS]
-}
+    fn new_resources(&self) -> Resources {
+        vec![0; self.kinds()]
+    }
 
-fn resource_kind(name: ResourceKind, count: ResourceAmount) -> Resources {
-    let mut resources: Resources = new_resources();
-    resources[name] = count;
-    resources
-}
-fn ore(count: ResourceAmount) -> Resources {
-    resource_kind(ORE, count)
+    /// Geodes if the blueprint knows about them, otherwise the last robot listed
+    fn default_target(&self) -> ResourceKind {
+        self.kind("geode").unwrap_or_else(|| {
+            self.costs
+                .iter()
+                .rposition(Option::is_some)
+                .expect("Blueprints have at least one robot")
+        })
+    }
 }
 
-fn clay(count: ResourceAmount) -> Resources {
-    resource_kind(CLAY, count)
+/// Writes the structured format read back by `parse_structured`
+impl Display for Blueprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let recipes = self
+            .costs
+            .iter()
+            .enumerate()
+            .filter_map(|(kind, costs)| Some((kind, costs.as_ref()?)))
+            .map(|(kind, costs)| {
+                let costs = costs
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &amount)| amount > 0)
+                    .map(|(component, amount)| format!("{amount} {}", self.names[component]))
+                    .join(" + ");
+                format!("{} = {costs}", self.names[kind])
+            })
+            .join("; ");
+        write!(f, "{}: {recipes}", self.id)
+    }
 }
 
-fn obsidian(count: ResourceAmount) -> Resources {
-    resource_kind(OBSIDIAN, count)
+fn resource_kind(kinds: usize, name: ResourceKind, count: ResourceAmount) -> Resources {
+    let mut resources = vec![0; kinds];
+    resources[name] = count;
+    resources
 }
 
 fn add(left: &Resources, right: &Resources) -> Resources {
-    let mut out = new_resources();
-    for name in RESOURCE_NAMES {
-        out[name] = left[name] + right[name];
-    }
-    out
+    left.iter().zip(right).map(|(l, r)| l + r).collect()
 }
 
 fn mul(resources: &Resources, factor: ResourceAmount) -> Resources {
-    let mut out = new_resources();
-    for name in RESOURCE_NAMES {
-        out[name] = resources[name] * factor;
-    }
-    out
+    resources.iter().map(|amount| amount * factor).collect()
 }
 
 fn sub(resources: &Resources, amount: &Resources) -> Resources {
@@ -70,52 +128,92 @@ fn produce(bank: &Resources, bots: &Resources, ticks: ResourceAmount) -> Resourc
     add(bank, &mul(bots, ticks))
 }
 
-fn blueprint(ore: Resources, clay: Resources, obsidian: Resources, geode: Resources) -> Blueprint {
-    let mut bp = new_blueprint();
-    bp[ORE] = ore;
-    bp[CLAY] = clay;
-    bp[OBSIDIAN] = obsidian;
-    bp[GEODE] = geode;
-    bp
+/// "4 ore" into its amount and resource name
+fn parse_cost(cost: &str) -> Result<(ResourceAmount, &str)> {
+    let (amount, name) = cost
+        .trim()
+        .split_once(' ')
+        .with_context(|| format!("Expected an amount and a resource in {cost:?}"))?;
+    let amount = amount
+        .parse()
+        .with_context(|| format!("Bad amount in {cost:?}"))?;
+    Ok((amount, name.trim()))
 }
 
-fn parse_blueprint(bp: &str) -> Result<Blueprint> {
-    let re = Regex::new(r"([0-9]+)")?;
+/// The puzzle's prose, "Each ore robot costs 4 ore. Each obsidian robot costs 3 ore and 14 clay."
+fn parse_blueprint(id: i32, body: &str) -> Result<Blueprint> {
+    let body = body.split_whitespace().join(" ");
+    let recipes = body
+        .split('.')
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .map(|sentence| {
+            let (robot, costs) = sentence
+                .strip_prefix("Each ")
+                .and_then(|recipe| recipe.split_once(" robot costs "))
+                .with_context(|| format!("Blueprint {id}: can't read {sentence:?}"))?;
+            let costs = costs.split(" and ").map(parse_cost).collect::<Result<_>>();
+            Ok((robot, costs.with_context(|| format!("Blueprint {id}"))?))
+        })
+        .collect::<Result<_>>()?;
+    Blueprint::new(id, recipes)
+}
 
-    let matches: Result<Vec<_>, _> = re
-        .captures_iter(bp)
-        .map(|m| m.get(1).expect("Expected match"))
-        .map(|m| m.as_str().parse::<i32>())
-        .collect();
-    let numbers = matches?;
-    if numbers.len() != 6 {
-        Err(anyhow!("Wrong number of resources: {:?}", numbers))
-    } else {
-        Ok(blueprint(
-            ore(numbers[0]),
-            ore(numbers[1]),
-            add(&ore(numbers[2]), &clay(numbers[3])),
-            add(&ore(numbers[4]), &obsidian(numbers[5])),
-        ))
-    }
+/// One blueprint per line as `1: ore = 4 ore; obsidian = 3 ore + 14 clay`, an empty list of
+/// costs makes a free robot.
+fn parse_structured(line: &str) -> Result<Blueprint> {
+    let (id, body) = line.split_once(':').context("Needed blueprint id")?;
+    let id = id.trim().parse()?;
+    let recipes = body
+        .split(';')
+        .map(|recipe| {
+            let (robot, costs) = recipe
+                .split_once('=')
+                .with_context(|| format!("Blueprint {id}: expected robot = costs in {recipe:?}"))?;
+            let costs = costs
+                .split('+')
+                .filter(|cost| !cost.trim().is_empty())
+                .map(parse_cost)
+                .collect::<Result<_>>();
+            Ok((
+                robot.trim(),
+                costs.with_context(|| format!("Blueprint {id}"))?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Blueprint::new(id, recipes)
 }
 
-fn parse_bp_line(bp: &str) -> Result<(i32, Blueprint)> {
-    let mut parts = bp.split(':');
-    let name_part = parts.next().context("Needed blueprint name")?;
+fn parse_bp_prose(bp: &str) -> Result<Blueprint> {
+    let (name_part, bp_part) = bp.split_once(':').context("Needed blueprint values part")?;
     let id_s = name_part
-        .split(' ')
+        .split_whitespace()
         .last()
         .context("Needed blueprint name")?;
     let id = id_s.parse()?;
-    let bp_part = parts.next().context("Needed blueprint values part")?;
-    let bp = parse_blueprint(bp_part)?;
-    Ok((id, bp))
+    parse_blueprint(id, bp_part)
 }
 
-fn parse_bps(bps: &str) -> Result<Vec<(i32, Blueprint)>> {
-    let bps: Result<Vec<_>> = bps.lines().map(parse_bp_line).collect();
-    bps
+/// Blueprints in the puzzle's prose, which may wrap over several lines, or the structured format
+fn parse_bps(bps: &str) -> Result<Vec<Blueprint>> {
+    if bps.trim_start().starts_with("Blueprint") {
+        bps.split("Blueprint")
+            .filter(|bp| !bp.trim().is_empty())
+            .map(parse_bp_prose)
+            .collect()
+    } else {
+        bps.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_structured)
+            .collect()
+    }
+}
+
+/// Start building a robot collecting `robot` during `minute`, counting from 1
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+struct Build {
+    minute: i32,
+    robot: ResourceKind,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -123,6 +221,21 @@ struct State {
     bank: Resources,
     bots: Resources,
     ticks: i32,
+    builds: Vec<Build>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+struct Stats {
+    states_checked: usize,
+    /// States dropped because even the upper bound couldn't beat the best found so far
+    pruned: usize,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Solution {
+    collected: ResourceAmount,
+    plan: Vec<Build>,
+    stats: Stats,
 }
 
 fn max_opt(
@@ -136,15 +249,18 @@ fn max_opt(
 }
 
 fn rounds_to_afford(bp: &Blueprint, bank: &Resources, bots: &Resources) -> CanBuild {
-    let mut out = new_can_build();
-    for bot_name in RESOURCE_NAMES {
+    let mut out = vec![None; bp.kinds()];
+    for (bot_name, costs) in bp.costs.iter().enumerate() {
+        let Some(costs) = costs else {
+            continue;
+        };
         let mut can_build = Some(0);
-        for component in RESOURCE_NAMES {
+        for (component, &cost) in costs.iter().enumerate() {
             // Component not required for this bot, or we can already afford it
-            if bp[bot_name][component] == 0 || bank[component] > bp[bot_name][component] {
+            if cost == 0 || bank[component] >= cost {
                 can_build = max_opt(can_build, Some(0));
             } else if bots[component] > 0 {
-                let missing_funds = bp[bot_name][component] - bank[component];
+                let missing_funds = cost - bank[component];
                 let rounds = if missing_funds % bots[component] > 0 {
                     missing_funds / bots[component] + 1
                 } else {
@@ -160,37 +276,70 @@ fn rounds_to_afford(bp: &Blueprint, bank: &Resources, bots: &Resources) -> CanBu
     out
 }
 
+/// The most of `target` that could possibly be collected.
+///
+/// Relaxes the rules so that every robot the bank can afford gets built each minute, without
+/// paying for any of them. That never falls behind a real factory, so the bound is admissible,
+/// but it still has to wait for the inputs of the target robot, like obsidian for geodes.
+/// Gives up early with a looser bound once that is already no better than `best`.
 fn upper_bound(
-    mut geodes_found: ResourceAmount,
-    mut geode_bots: ResourceAmount,
+    bp: &Blueprint,
+    target: ResourceKind,
+    bank: &Resources,
+    bots: &Resources,
     remaining_time: i32,
+    best: ResourceAmount,
 ) -> ResourceAmount {
+    // Cheap first: a new target robot every single minute
+    let found =
+        bank[target] + bots[target] * remaining_time + remaining_time * (remaining_time - 1) / 2;
+    if found <= best {
+        return found;
+    }
+    let mut bank = bank.clone();
+    let mut bots = bots.clone();
+    let mut affordable = vec![false; bp.kinds()];
     for _ in 0..remaining_time {
-        geodes_found += geode_bots;
-        geode_bots += 1;
+        for (can_afford, costs) in affordable.iter_mut().zip(&bp.costs) {
+            *can_afford = costs
+                .as_ref()
+                .is_some_and(|costs| bank.iter().zip(costs).all(|(have, cost)| have >= cost));
+        }
+        for ((have, count), &built) in bank.iter_mut().zip(bots.iter_mut()).zip(&affordable) {
+            *have += *count;
+            if built {
+                *count += 1;
+            }
+        }
     }
-    geodes_found
+    bank[target]
 }
 
-fn search(bp: &Blueprint, ticks: i32) -> i32 {
+/// The most of `target` the blueprint can collect in `ticks` minutes, and how to get it
+fn search(bp: &Blueprint, target: ResourceKind, ticks: i32) -> Solution {
+    let kinds = bp.kinds();
     let initial = State {
-        bank: new_resources(),
-        bots: ore(1),
+        bank: bp.new_resources(),
+        bots: resource_kind(kinds, 0, 1),
         ticks: 0,
+        builds: Vec::new(),
     };
     let mut stack = vec![initial];
-    let mut best = 0;
-    let mut max_cost_by_resource = new_resources();
-    for bot_kind in RESOURCE_NAMES {
-        for component in RESOURCE_NAMES {
-            max_cost_by_resource[component] =
-                max(max_cost_by_resource[component], bp[bot_kind][component]);
+    let mut best = Solution {
+        collected: 0,
+        plan: Vec::new(),
+        stats: Stats::default(),
+    };
+    // No point collecting more of anything per minute than a single robot can use up
+    let mut max_cost_by_resource = bp.new_resources();
+    for costs in bp.costs.iter().flatten() {
+        for (component, &cost) in costs.iter().enumerate() {
+            max_cost_by_resource[component] = max(max_cost_by_resource[component], cost);
         }
     }
-    let mut states_checked = 0;
 
     while let Some(state) = stack.pop() {
-        states_checked += 1;
+        best.stats.states_checked += 1;
         if state.ticks > ticks {
             panic!("Encountered expired state {:?}", state);
         }
@@ -198,111 +347,568 @@ fn search(bp: &Blueprint, ticks: i32) -> i32 {
         let rounds = rounds_to_afford(bp, &state.bank, &state.bots);
 
         // Calculate the no more choices approach directly
-        let guaranteed_geodes = state.bank[GEODE] + remainder * state.bots[GEODE];
-        if guaranteed_geodes > best {
-            best = guaranteed_geodes;
+        let guaranteed = state.bank[target] + remainder * state.bots[target];
+        if guaranteed > best.collected {
+            best.collected = guaranteed;
+            best.plan = state.builds.clone();
         }
-        if remainder == 0 || upper_bound(state.bank[GEODE], state.bots[GEODE], remainder) < best {
+        if remainder == 0 {
+            continue;
+        }
+        if upper_bound(
+            bp,
+            target,
+            &state.bank,
+            &state.bots,
+            remainder,
+            best.collected,
+        ) <= best.collected
+        {
+            best.stats.pruned += 1;
             continue;
         }
 
         // Attempt to build some bots -- we can only build 1 at a time
-        for &bot_kind in RESOURCE_NAMES.iter().rev() {
-            if let Some(time_needed) = rounds[bot_kind] {
+        for bot_kind in (0..kinds).rev() {
+            if let (Some(time_needed), Some(expense)) = (rounds[bot_kind], &bp.costs[bot_kind]) {
                 if time_needed < remainder
-                    && (state.bots[bot_kind] < max_cost_by_resource[bot_kind] || bot_kind == GEODE)
+                    && (state.bots[bot_kind] < max_cost_by_resource[bot_kind] || bot_kind == target)
                 {
                     let income = produce(&state.bank, &state.bots, time_needed + 1);
-                    let expense = &bp[bot_kind];
+                    let minute = state.ticks + time_needed + 1;
+                    let mut builds = state.builds.clone();
+                    builds.push(Build {
+                        minute,
+                        robot: bot_kind,
+                    });
                     stack.push(State {
                         bank: sub(&income, expense),
-                        bots: add(&state.bots, &resource_kind(bot_kind, 1)),
-                        ticks: state.ticks + time_needed + 1,
+                        bots: add(&state.bots, &resource_kind(kinds, bot_kind, 1)),
+                        ticks: minute,
+                        builds,
                     })
                 }
             }
         }
     }
 
-    println!("Investigated {states_checked} states");
     best
 }
 
+/// What happened during one minute of following a plan
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Minute {
+    minute: i32,
+    built: Option<ResourceKind>,
+    /// Robots working during the minute
+    bots: Resources,
+    /// Resources at the end of the minute
+    bank: Resources,
+}
+
+/// Follow `plan` for `ticks` minutes, failing if it builds something it can't afford
+fn replay(bp: &Blueprint, plan: &[Build], ticks: i32) -> Result<Vec<Minute>> {
+    let mut bank = bp.new_resources();
+    let mut bots = resource_kind(bp.kinds(), 0, 1);
+    let mut builds = plan.iter().peekable();
+    let mut minutes = Vec::new();
+    for minute in 1..=ticks {
+        let built = builds
+            .next_if(|build| build.minute == minute)
+            .map(|build| build.robot);
+        if let Some(robot) = built {
+            let costs = bp.costs[robot].as_ref().with_context(|| {
+                format!("Minute {minute}: no robot collects {}", bp.names[robot])
+            })?;
+            bank = sub(&bank, costs);
+            if bank.iter().any(|&amount| amount < 0) {
+                return Err(anyhow!(
+                    "Minute {minute}: can't afford a {} robot",
+                    bp.names[robot]
+                ));
+            }
+        }
+        bank = add(&bank, &bots);
+        minutes.push(Minute {
+            minute,
+            built,
+            bots: bots.clone(),
+            bank: bank.clone(),
+        });
+        if let Some(robot) = built {
+            bots[robot] += 1;
+        }
+    }
+    if let Some(build) = builds.next() {
+        return Err(anyhow!(
+            "Minute {}: out of time or out of order",
+            build.minute
+        ));
+    }
+    Ok(minutes)
+}
+
+/// Describe the minutes of a replayed plan in the words of the puzzle's example
+fn walkthrough(bp: &Blueprint, minutes: &[Minute]) -> String {
+    // Geodes are cracked rather than collected
+    let robot = |kind: usize| match bp.names[kind].as_str() {
+        "geode" => "geode-cracking robot".to_string(),
+        name => format!("{name}-collecting robot"),
+    };
+    let article = |noun: &str| match noun.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a",
+    };
+    let mut out = String::new();
+    for minute in minutes {
+        out += &format!("== Minute {} ==\n", minute.minute);
+        if let Some(kind) = minute.built {
+            let costs = bp.costs[kind].as_ref().map_or(String::new(), |costs| {
+                costs
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &amount)| amount > 0)
+                    .map(|(component, amount)| format!("{amount} {}", bp.names[component]))
+                    .join(" and ")
+            });
+            let robot = robot(kind);
+            out += &format!(
+                "Spend {costs} to start building {} {robot}.\n",
+                article(&robot)
+            );
+        }
+        for (kind, &count) in minute.bots.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let plural = if count == 1 { "" } else { "s" };
+            let name = &bp.names[kind];
+            let have = minute.bank[kind];
+            if name == "geode" {
+                let verb = if count == 1 { "cracks" } else { "crack" };
+                let opened = if have == 1 { "" } else { "s" };
+                out += &format!(
+                    "{count} {}{plural} {verb} {count} {name}{plural}; \
+                     you now have {have} open {name}{opened}.\n",
+                    robot(kind)
+                );
+            } else {
+                let verb = if count == 1 { "collects" } else { "collect" };
+                out += &format!(
+                    "{count} {}{plural} {verb} {count} {name}; you now have {have} {name}.\n",
+                    robot(kind)
+                );
+            }
+        }
+        if let Some(kind) = minute.built {
+            out += &format!(
+                "The new {} is ready; you now have {} of them.\n",
+                robot(kind),
+                minute.bots[kind] + 1
+            );
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// The outcome of searching one blueprint
+struct Evaluation {
+    id: i32,
+    solution: Solution,
+    elapsed: Duration,
+}
+
+impl Evaluation {
+    fn quality(&self) -> i32 {
+        self.id * self.solution.collected
+    }
+}
+
+/// Search every blueprint for its target in parallel, calling `on_done` as each one finishes.
+/// The results come back in the same order as `jobs`.
+fn evaluate(
+    jobs: &[(&Blueprint, ResourceKind)],
+    ticks: i32,
+    on_done: impl Fn(&Evaluation) + Sync,
+) -> Vec<Evaluation> {
+    jobs.par_iter()
+        .map(|&(bp, target)| {
+            let start = Instant::now();
+            let solution = search(bp, target, ticks);
+            let evaluation = Evaluation {
+                id: bp.id,
+                solution,
+                elapsed: start.elapsed(),
+            };
+            on_done(&evaluation);
+            evaluation
+        })
+        .collect()
+}
+
+fn results_table(evaluations: &[Evaluation]) -> String {
+    let mut out = format!(
+        "{:>4} {:>9} {:>7} {:>10} {:>9}\n",
+        "id", "collected", "quality", "time", "states"
+    );
+    for evaluation in evaluations {
+        out += &format!(
+            "{:>4} {:>9} {:>7} {:>10} {:>9}\n",
+            evaluation.id,
+            evaluation.solution.collected,
+            evaluation.quality(),
+            format!("{:.1?}", evaluation.elapsed),
+            evaluation.solution.stats.states_checked
+        );
+    }
+    out
+}
+
+/// Blueprint ids as a comma separated list of ids and inclusive ranges, like `1,4-6`
+fn parse_subset(subset: &str) -> Result<Vec<i32>> {
+    let mut ids = Vec::new();
+    for part in subset.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => ids.extend(from.trim().parse::<i32>()?..=to.trim().parse()?),
+            None => ids.push(part.trim().parse()?),
+        }
+    }
+    Ok(ids)
+}
+
+#[derive(Default)]
+struct Options {
+    target: Option<String>,
+    minutes: Option<i32>,
+    subset: Option<Vec<i32>>,
+    table: bool,
+    progress: bool,
+    walkthrough: bool,
+}
+
+impl Options {
+    fn from_args() -> Result<Options> {
+        let mut options = Options::default();
+        for arg in std::env::args().skip(1) {
+            match arg.split_once('=') {
+                Some(("target", name)) => options.target = Some(name.to_string()),
+                Some(("minutes", minutes)) => options.minutes = Some(minutes.parse()?),
+                Some(("blueprints", subset)) => options.subset = Some(parse_subset(subset)?),
+                None if arg == "table" => options.table = true,
+                None if arg == "progress" => options.progress = true,
+                None if arg == "walkthrough" => options.walkthrough = true,
+                _ => return Err(anyhow!("Unknown option {arg}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Evaluate `jobs` for `ticks` minutes, printing whatever `options` ask for along the way
+fn run(
+    jobs: &[(&Blueprint, ResourceKind)],
+    ticks: i32,
+    options: &Options,
+) -> Result<Vec<Evaluation>> {
+    let done = AtomicUsize::new(0);
+    let evaluations = evaluate(jobs, ticks, |evaluation| {
+        let done = done.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+        if options.progress {
+            eprintln!(
+                "[{done}/{}] Blueprint {}: {} in {:.1?}",
+                jobs.len(),
+                evaluation.id,
+                evaluation.solution.collected,
+                evaluation.elapsed
+            );
+        }
+    });
+    if options.table {
+        println!("{ticks} minutes:\n{}", results_table(&evaluations));
+    }
+    if options.walkthrough {
+        for (&(bp, _), evaluation) in jobs.iter().zip(&evaluations) {
+            let Solution {
+                collected, stats, ..
+            } = &evaluation.solution;
+            println!(
+                "Blueprint {}: {collected} after checking {} states, {} pruned\n",
+                bp.id, stats.states_checked, stats.pruned
+            );
+            let minutes = replay(bp, &evaluation.solution.plan, ticks)?;
+            print!("{}", walkthrough(bp, &minutes));
+        }
+    }
+    Ok(evaluations)
+}
+
+fn main() -> Result<()> {
+    let options = Options::from_args()?;
+    let input = read_stdin()?;
+    let blueprints = parse_bps(input.as_str())?;
+    let jobs = blueprints
+        .iter()
+        .map(|bp| match &options.target {
+            Some(name) => bp
+                .kind(name)
+                .map(|target| (bp, target))
+                .with_context(|| format!("Blueprint {} has no {name}", bp.id)),
+            None => Ok((bp, bp.default_target())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if options.minutes.is_some() || options.subset.is_some() {
+        let selected = match &options.subset {
+            None => jobs,
+            Some(ids) => ids
+                .iter()
+                .map(|&id| {
+                    jobs.iter()
+                        .find(|(bp, _)| bp.id == id)
+                        .copied()
+                        .with_context(|| format!("No blueprint {id}"))
+                })
+                .collect::<Result<_>>()?,
+        };
+        let evaluations = run(&selected, options.minutes.unwrap_or(24), &options)?;
+        let quality: i32 = evaluations.iter().map(Evaluation::quality).sum();
+        let product: i64 = evaluations
+            .iter()
+            .map(|evaluation| evaluation.solution.collected as i64)
+            .product();
+        println!("Total quality: {quality}");
+        println!("Product: {product}");
+        return Ok(());
+    }
+
+    let evaluations = run(&jobs, 24, &options)?;
+    let qualities: i32 = evaluations.iter().map(Evaluation::quality).sum();
+    println!("Part 1: {qualities}");
+    let first = &jobs[..jobs.len().min(3)];
+    let qualities: i32 = run(first, 32, &options)?
+        .iter()
+        .map(|evaluation| evaluation.solution.collected)
+        .product();
+    println!("Part 2: {qualities}");
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        add, clay, new_resources, obsidian, ore, parse_blueprint, parse_bps, rounds_to_afford,
-        search, CLAY, GEODE, OBSIDIAN, ORE,
+        evaluate, parse_blueprint, parse_bps, parse_structured, parse_subset, replay,
+        results_table, rounds_to_afford, search, upper_bound, walkthrough, Build,
     };
+    use std::sync::Mutex;
 
     const EXAMPLE: &str = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.
 Blueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.
 ";
 
+    const SAMPLE: &str = "Each ore robot costs 4 ore. Each clay robot costs 4 ore. Each obsidian robot costs 4 ore and 12 clay. Each geode robot costs 4 ore and 19 obsidian.";
+
     #[test]
     fn test_parse_bp() {
-        let sample = "Each ore robot costs 4 ore. Each clay robot costs 4 ore. Each obsidian robot costs 4 ore and 12 clay. Each geode robot costs 4 ore and 19 obsidian.";
-        let bp = parse_blueprint(sample).unwrap();
-
-        assert_eq!(bp[ORE][ORE], 4);
-        assert_eq!(bp[CLAY][ORE], 4);
-        assert_eq!(bp[OBSIDIAN][ORE], 4);
-        assert_eq!(bp[OBSIDIAN][CLAY], 12);
-        assert_eq!(bp[GEODE][ORE], 4);
-        assert_eq!(bp[GEODE][OBSIDIAN], 19);
+        let bp = parse_blueprint(1, SAMPLE).unwrap();
+        let [ore, clay, obsidian, geode] =
+            ["ore", "clay", "obsidian", "geode"].map(|name| bp.kind(name).unwrap());
+        let cost = |bot: usize, component: usize| bp.costs[bot].as_ref().unwrap()[component];
+
+        assert_eq!(cost(ore, ore), 4);
+        assert_eq!(cost(clay, ore), 4);
+        assert_eq!(cost(obsidian, ore), 4);
+        assert_eq!(cost(obsidian, clay), 12);
+        assert_eq!(cost(geode, ore), 4);
+        assert_eq!(cost(geode, obsidian), 19);
+        assert_eq!(bp.default_target(), geode);
     }
 
     #[test]
     fn test_parse_bps() {
         let bp = parse_bps(EXAMPLE).unwrap();
         assert_eq!(bp.len(), 2);
-        assert_eq!(bp[0].0, 1);
+        assert_eq!(bp[0].id, 1);
+        // The puzzle text wraps each blueprint over several lines
+        let wrapped = EXAMPLE.replace(". ", ".\n  ");
+        assert_eq!(parse_bps(&wrapped).unwrap(), bp);
+        assert!(parse_bps("Blueprint 1: Each ore robot costs four ore.").is_err());
+        assert!(parse_bps("Blueprint 1: Every ore robot costs 4 ore.").is_err());
+    }
+
+    #[test]
+    fn test_structured_round_trip() {
+        let bps = parse_bps(EXAMPLE).unwrap();
+        assert_eq!(
+            bps[0].to_string(),
+            "1: ore = 4 ore; clay = 2 ore; obsidian = 3 ore + 14 clay; geode = 2 ore + 7 obsidian"
+        );
+        let structured = bps.iter().map(|bp| format!("{bp}\n")).collect::<String>();
+        assert_eq!(parse_bps(&structured).unwrap(), bps);
+        assert!(parse_structured("1: ore = 4 ore; ore = 2 ore").is_err());
     }
 
     #[test]
     fn test_rounds_to_afford() {
-        let sample = "Each ore robot costs 4 ore. Each clay robot costs 4 ore. Each obsidian robot costs 4 ore and 12 clay. Each geode robot costs 4 ore and 19 obsidian.";
-        let bp = parse_blueprint(sample).unwrap();
-        let rounds = rounds_to_afford(&bp, &new_resources(), &add(&ore(1), &clay(1)));
-        assert_eq!(rounds[ORE], Some(4));
-        assert_eq!(rounds[CLAY], Some(4));
-        assert_eq!(rounds[OBSIDIAN], Some(12));
-        let rounds = rounds_to_afford(&bp, &new_resources(), &add(&ore(1), &obsidian(2)));
-        assert_eq!(rounds[GEODE], Some(10));
+        let bp = parse_blueprint(1, SAMPLE).unwrap();
+        let rounds = rounds_to_afford(&bp, &vec![0; 4], &vec![1, 1, 0, 0]);
+        assert_eq!(rounds[0], Some(4));
+        assert_eq!(rounds[1], Some(4));
+        assert_eq!(rounds[2], Some(12));
+        let rounds = rounds_to_afford(&bp, &vec![0; 4], &vec![1, 0, 2, 0]);
+        assert_eq!(rounds[3], Some(10));
+        let rounds = rounds_to_afford(&bp, &vec![4, 0, 0, 0], &vec![0; 4]);
+        assert_eq!(rounds[0], Some(0));
     }
 
     #[test]
     fn test_example() {
         let blueprints = parse_bps(EXAMPLE).unwrap();
-        let result = search(&blueprints[0].1, 24);
+        let result = search(&blueprints[0], 3, 24).collected;
         assert_eq!(result, 9);
-        let result = search(&blueprints[1].1, 24);
+        let result = search(&blueprints[1], 3, 24).collected;
         assert_eq!(result, 12);
     }
 
     #[test]
     fn test_example_part_2() {
         let blueprints = parse_bps(EXAMPLE).unwrap();
-        let result = search(&blueprints[0].1, 32);
+        let result = search(&blueprints[0], 3, 32).collected;
         assert_eq!(result, 56);
-        let result = search(&blueprints[1].1, 32);
+        let result = search(&blueprints[1], 3, 32).collected;
         assert_eq!(result, 62);
     }
-}
 
-fn main() -> Result<()> {
-    let input = read_stdin()?;
-    let blueprints = parse_bps(input.as_str())?;
-    let qualities: i32 = blueprints
-        .iter()
-        .map(|&(id, bp)| search(&bp, 24) * id)
-        .sum();
-    println!("Part 1: {qualities}");
-    let qualities: i32 = blueprints
-        .iter()
-        .take(3)
-        .map(|&(_, bp)| search(&bp, 32))
-        .product();
-    println!("Part 2: {qualities}");
-    Ok(())
+    #[test]
+    fn test_other_resources() {
+        // Five resources, where crystals need both clay and obsidian and are the goal
+        let bp = parse_structured(
+            "7: ore = 2 ore; clay = 2 ore; obsidian = 3 ore + 4 clay; \
+             crystal = 2 clay + 3 obsidian; geode = 10 ore + 10 obsidian",
+        )
+        .unwrap();
+        assert_eq!(bp.default_target(), 4);
+        let crystal = bp.kind("crystal").unwrap();
+        let crystals = search(&bp, crystal, 24).collected;
+        assert!(crystals > 0);
+        // Intermediate resources can be targets too, and clay is never harder to get than crystal
+        assert!(search(&bp, bp.kind("clay").unwrap(), 24).collected > crystals);
+        // A robot made of something no robot collects can never be built
+        let bp = parse_structured("1: ore = 1 ore; gold = 1 ore + 1 silver").unwrap();
+        assert_eq!(bp.kinds(), 3);
+        assert_eq!(search(&bp, 1, 24).collected, 0);
+    }
+
+    #[test]
+    fn test_plan_replays() {
+        let blueprints = parse_bps(EXAMPLE).unwrap();
+        for (bp, expected) in blueprints.iter().zip([9, 12]) {
+            let solution = search(bp, 3, 24);
+            let minutes = replay(bp, &solution.plan, 24).unwrap();
+            assert_eq!(minutes.last().unwrap().bank[3], expected);
+            assert!(solution.stats.pruned > 0);
+            assert!(solution.stats.states_checked > solution.stats.pruned);
+        }
+    }
+
+    #[test]
+    fn test_example_walkthrough() {
+        let bp = &parse_bps(EXAMPLE).unwrap()[0];
+        // The build order from the puzzle's walkthrough of blueprint 1
+        let plan = [
+            (3, 1),
+            (5, 1),
+            (7, 1),
+            (11, 2),
+            (12, 1),
+            (15, 2),
+            (18, 3),
+            (21, 3),
+        ]
+        .map(|(minute, robot)| Build { minute, robot });
+        let minutes = replay(bp, &plan, 24).unwrap();
+        assert_eq!(minutes[23].bank, vec![6, 41, 8, 9]);
+        let text = walkthrough(bp, &minutes);
+        assert!(text.starts_with(
+            "\
+== Minute 1 ==
+1 ore-collecting robot collects 1 ore; you now have 1 ore.
+
+== Minute 2 ==
+1 ore-collecting robot collects 1 ore; you now have 2 ore.
+
+== Minute 3 ==
+Spend 2 ore to start building a clay-collecting robot.
+1 ore-collecting robot collects 1 ore; you now have 1 ore.
+The new clay-collecting robot is ready; you now have 1 of them.
+"
+        ));
+        assert!(text.contains(
+            "\
+== Minute 11 ==
+Spend 3 ore and 14 clay to start building an obsidian-collecting robot.
+1 ore-collecting robot collects 1 ore; you now have 2 ore.
+3 clay-collecting robots collect 3 clay; you now have 4 clay.
+The new obsidian-collecting robot is ready; you now have 1 of them.
+"
+        ));
+        assert!(text.ends_with(
+            "\
+== Minute 24 ==
+1 ore-collecting robot collects 1 ore; you now have 6 ore.
+4 clay-collecting robots collect 4 clay; you now have 41 clay.
+2 obsidian-collecting robots collect 2 obsidian; you now have 8 obsidian.
+2 geode-cracking robots crack 2 geodes; you now have 9 open geodes.
+
+"
+        ));
+        let too_early = [Build {
+            minute: 2,
+            robot: 1,
+        }];
+        assert!(replay(bp, &too_early, 24).is_err());
+    }
+
+    #[test]
+    fn test_upper_bound_waits_for_obsidian() {
+        let bp = &parse_bps(EXAMPLE).unwrap()[0];
+        // Without any obsidian robots, it takes a while before the first geode robot
+        let bound = upper_bound(bp, 3, &vec![0; 4], &vec![1, 0, 0, 0], 24, 0);
+        assert!(bound >= 9);
+        assert!(bound < 23 * 24 / 2);
+        assert_eq!(upper_bound(bp, 3, &vec![0; 4], &vec![1, 0, 0, 0], 5, 0), 0);
+        // Already beaten, so only the cheap bound is worked out
+        let cheap = upper_bound(bp, 3, &vec![0; 4], &vec![1, 0, 0, 0], 24, 1000);
+        assert_eq!(cheap, 23 * 24 / 2);
+    }
+
+    #[test]
+    fn test_evaluate_in_parallel() {
+        let blueprints = parse_bps(EXAMPLE).unwrap();
+        let jobs: Vec<_> = blueprints.iter().map(|bp| (bp, 3)).collect();
+        let finished = Mutex::new(Vec::new());
+        let evaluations = evaluate(&jobs, 24, |evaluation| {
+            finished.lock().unwrap().push(evaluation.id);
+        });
+        let results: Vec<_> = evaluations
+            .iter()
+            .map(|e| (e.id, e.solution.collected, e.quality()))
+            .collect();
+        assert_eq!(results, vec![(1, 9, 9), (2, 12, 24)]);
+        let mut finished = finished.into_inner().unwrap();
+        finished.sort();
+        assert_eq!(finished, vec![1, 2]);
+
+        let table = results_table(&evaluations);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[0], "  id collected quality       time    states");
+        assert!(lines[2].starts_with("   2        12      24 "));
+        assert!(lines[2].ends_with(&evaluations[1].solution.stats.states_checked.to_string()));
+    }
+
+    #[test]
+    fn test_parse_subset() {
+        assert_eq!(parse_subset("1,4-6, 9").unwrap(), vec![1, 4, 5, 6, 9]);
+        assert!(parse_subset("1,x").is_err());
+    }
 }
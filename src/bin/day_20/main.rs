@@ -1,61 +1,74 @@
-use anyhow::{Context, Result};
-use std::collections::VecDeque;
-use itertools::Itertools;
+use anyhow::{anyhow, Context, Result};
+use aoc::indexed_list::{Handle, IndexedList};
 use aoc::io::read_stdin;
 
-fn parse(input: &str) -> Result<VecDeque<(usize, i64)>> {
-    input.lines()
+const DECRYPTION_KEY: i64 = 811589153;
+
+fn parse(input: &str) -> Result<Vec<i64>> {
+    input
+        .lines()
         .filter(|line| !line.is_empty())
-        .enumerate()
-        .map(|(idx, line)| {
-            let val = line.parse::<i64>()?;
-            Ok((idx, val))
-        })
+        .map(|line| Ok(line.parse::<i64>()?))
         .collect()
 }
 
-fn mix(deq: &mut VecDeque<(usize, i64)>) {
-    for i in 0..deq.len() {
-        let (now_location, _) = deq.iter()
-            .find_position(|(old_loc, _)| *old_loc == i)
-            .unwrap();
-        let (idx, val) = deq.remove(now_location).unwrap();
-        let new_loc = (now_location as i64 + val).rem_euclid(deq.len() as i64) as usize;
-        deq.insert(new_loc, (idx, val));
+/// Move every number as many places along as its value, in the order of `handles`
+fn mix(list: &mut IndexedList<i64>, handles: &[Handle]) {
+    if list.len() <= 1 {
+        return;
+    }
+    for &handle in handles {
+        let now_location = list.index_of(handle);
+        let val = *list.value(handle).expect("Mixing never removes numbers");
+        // Moving past the other numbers only, so the cycle is one shorter than the list
+        let new_loc = (now_location as i64 + val).rem_euclid(list.len() as i64 - 1) as usize;
+        list.move_to(handle, new_loc);
     }
 }
 
-fn part_1(input: &str) -> Result<i64> {
-    let mut deq = parse(input)?;
-    mix(&mut deq);
-    let (zero_loc, _) = deq.iter()
-        .find_position(|&(_, val)| *val == 0).context("Lost 0")?;
-    let n =
-        (1..=3).into_iter()
-            .map(|idx| (idx * 1000 + zero_loc).rem_euclid(deq.len()))
-            .map(|idx| deq.get(idx).unwrap().1)
-            .sum();
+/// Sum of the numbers 1000, 2000 and 3000 after the 0, once the file is mixed `rounds` times
+fn decrypt(values: &[i64], key: i64, rounds: usize) -> Result<i64> {
+    let mut list = IndexedList::new();
+    let handles: Vec<Handle> = values.iter().map(|val| list.push(val * key)).collect();
+    for _ in 0..rounds {
+        mix(&mut list, &handles);
+    }
+    let zero = handles
+        .iter()
+        .find(|&&handle| list.value(handle) == Some(&0))
+        .context("Lost 0")?;
+    let zero_loc = list.index_of(*zero);
+    let n = (1..=3)
+        .map(|idx| (idx * 1000 + zero_loc).rem_euclid(list.len()))
+        .map(|idx| list.get(idx).unwrap())
+        .sum();
     Ok(n)
 }
 
-fn part_2(input: &str) -> Result<i64> {
-    let mut deq = parse(input)?.into_iter().map(|(idx, val)| (idx, val * 811589153)).collect();
-    for _ in 0..10 {
-        mix(&mut deq);
-    }
-    let (zero_loc, _) = deq.iter()
-        .find_position(|&(_, val)| *val == 0).context("Lost 0")?;
-    let n =
-        (1..=3).into_iter()
-            .map(|idx| (idx * 1000 + zero_loc).rem_euclid(deq.len()))
-            .map(|idx| deq.get(idx).unwrap().1)
-            .sum();
-    Ok(n)
+fn part_1(input: &str) -> Result<i64> {
+    decrypt(&parse(input)?, 1, 1)
 }
 
+fn part_2(input: &str) -> Result<i64> {
+    decrypt(&parse(input)?, DECRYPTION_KEY, 10)
+}
 
 fn main() -> Result<()> {
+    let mut custom = None;
+    for arg in std::env::args().skip(1) {
+        let (key, rounds) = custom.get_or_insert((DECRYPTION_KEY, 10));
+        match arg.split_once('=') {
+            Some(("key", value)) => *key = value.parse()?,
+            Some(("rounds", value)) => *rounds = value.parse()?,
+            _ => return Err(anyhow!("Unknown option {arg}")),
+        }
+    }
     let s = read_stdin()?;
+    if let Some((key, rounds)) = custom {
+        let n = decrypt(&parse(s.as_str())?, key, rounds)?;
+        println!("{n}");
+        return Ok(());
+    }
     let n = part_1(s.as_str())?;
     println!("{n}");
     let n = part_2(s.as_str())?;
@@ -65,8 +78,9 @@ fn main() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
+    use crate::{decrypt, mix, parse, part_1, part_2};
+    use aoc::indexed_list::IndexedList;
     use std::collections::VecDeque;
-    use crate::{mix, parse, part_1, part_2};
 
     const EXAMPLE: &str = "1
 2
@@ -79,21 +93,35 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let deq = parse(EXAMPLE).unwrap();
-        assert_eq!(deq, vec![
-            1, 2, -3, 3, -2, 0, 4
-        ].into_iter().enumerate().collect::<VecDeque<_>>())
+        let values = parse(EXAMPLE).unwrap();
+        assert_eq!(values, vec![1, 2, -3, 3, -2, 0, 4])
     }
 
     #[test]
     fn test_mix() {
-        let mut deq = parse(EXAMPLE).unwrap();
-        mix(&mut deq);
-        let mixed: Vec<_> = deq.iter()
-            .map(|(_, val)| *val)
-            .collect();
+        let values = parse(EXAMPLE).unwrap();
+        let mut list = IndexedList::new();
+        let handles: Vec<_> = values.iter().map(|&val| list.push(val)).collect();
+        mix(&mut list, &handles);
+        let mixed: Vec<_> = list.iter().copied().collect();
         assert_eq!(mixed, vec![-2, 1, 2, -3, 4, 0, 3]);
+    }
 
+    #[test]
+    fn test_mix_matches_deque() {
+        // The straightforward way of mixing, looking for each number and moving it
+        let values: Vec<i64> = (0..200).map(|i| (i * 7919 % 401) - 200).collect();
+        let mut deq: VecDeque<(usize, i64)> = values.iter().copied().enumerate().collect();
+        for i in 0..deq.len() {
+            let now_location = deq.iter().position(|&(idx, _)| idx == i).unwrap();
+            let (idx, val) = deq.remove(now_location).unwrap();
+            let new_loc = (now_location as i64 + val).rem_euclid(deq.len() as i64) as usize;
+            deq.insert(new_loc, (idx, val));
+        }
+        let mut list = IndexedList::new();
+        let handles: Vec<_> = values.iter().map(|&val| list.push(val)).collect();
+        mix(&mut list, &handles);
+        assert!(list.iter().eq(deq.iter().map(|(_, val)| val)));
     }
 
     #[test]
@@ -106,4 +134,12 @@ mod tests {
         assert_eq!(part_2(EXAMPLE).unwrap(), 1623178306);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_decrypt_parameters() {
+        let values = parse(EXAMPLE).unwrap();
+        assert_eq!(decrypt(&values, 1, 1).unwrap(), 3);
+        assert_eq!(decrypt(&values, 811589153, 10).unwrap(), 1623178306);
+        assert_eq!(decrypt(&values, 1, 0).unwrap(), -2 + 3 + -3);
+        assert!(decrypt(&[1, 2], 1, 1).is_err());
+    }
+}
@@ -126,8 +126,7 @@ fn run_part_1(input: &str) -> i64 {
         dir = dir.next();
     }
     let elves = board.len() as i64;
-    let rect = Rect::bound(board.iter().cloned());
-    rect.area() - elves
+    Rect::bound(board.iter().cloned()).map_or(0, |rect| rect.area()) - elves
 }
 
 fn run_part_2(input: &str) -> i64 {
@@ -1,51 +1,31 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use aoc::balanced::Snafu;
 use aoc::io::read_stdin;
 
-const SNAFU_STR: [char; 5] = ['0', '1', '2', '=', '-'];
-
-fn snafu_to_dec(snafu: &str) -> i64 {
-    let mut accum = 0;
-    for ch in snafu.chars() {
-        accum *= 5;
-        accum += match ch {
-            '2' => 2,
-            '1' => 1,
-            '0' => 0,
-            '-' => -1,
-            '=' => -2,
-            _ => panic!("Unknown snafu digit: {ch}"),
-        }
-    }
-    accum
-}
-
-fn dec_to_snafu(mut dec: i64) -> String {
-    let mut out = String::new();
-
-    while dec != 0 {
-        let rem = dec.rem_euclid(5) as usize;
-        out.push(SNAFU_STR[rem]);
-        dec = (2 + dec) / 5;
-    }
-
-    out.chars().rev().collect()
+/// Add up the fuel requirements, one SNAFU number per line
+fn fuel_sum(input: &str) -> Result<Snafu> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            line.parse::<Snafu>()
+                .with_context(|| format!("line {}", i + 1))
+        })
+        .sum()
 }
 
 fn main() -> Result<()> {
     let inp = read_stdin()?;
-    let sum: i64 = inp
-        .lines()
-        .filter(|line| !line.is_empty())
-        .map(snafu_to_dec)
-        .sum();
-    let snafu = dec_to_snafu(sum);
+    let snafu = fuel_sum(&inp)?;
     println!("{snafu}");
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{dec_to_snafu, snafu_to_dec};
+    use crate::fuel_sum;
+    use aoc::balanced::Snafu;
 
     const EXAMPLES: [(&str, i64); 13] = [
         ("1=-0-2", 1747),
@@ -66,14 +46,29 @@ mod tests {
     #[test]
     fn test_from_snafu() {
         for (snafu, dec) in EXAMPLES {
-            assert_eq!(snafu_to_dec(snafu), dec);
+            let parsed: Snafu = snafu.parse().unwrap();
+            assert_eq!(i64::try_from(&parsed).unwrap(), dec);
         }
     }
 
     #[test]
     fn test_to_snafu() {
         for (snafu, dec) in EXAMPLES {
-            assert_eq!(dec_to_snafu(dec), snafu);
+            assert_eq!(Snafu::from(dec).to_string(), snafu);
         }
     }
+
+    #[test]
+    fn test_fuel_sum() {
+        let input: String = EXAMPLES
+            .iter()
+            .map(|(snafu, _)| format!("{snafu}\n"))
+            .collect();
+        assert_eq!(fuel_sum(&input).unwrap().to_string(), "2=-1=0");
+        let err = fuel_sum("1=\n12\n1x\n").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 3: Unknown digit 'x' at column 2 of \"1x\""
+        );
+    }
 }
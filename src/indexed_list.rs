@@ -0,0 +1,344 @@
+/// Marks a missing child or parent
+const NIL: usize = usize::MAX;
+
+/// Refers to an element of an `IndexedList` for as long as it stays in the list, wherever it moves.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct Handle(usize);
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: Option<T>,
+    priority: u64,
+    size: usize,
+    left: usize,
+    right: usize,
+    parent: usize,
+}
+
+/// A sequence with `O(log n)` insertion and removal at any position, and lookup of the current
+/// position of an element through its `Handle`.
+///
+/// Implemented as an implicit treap: a binary tree ordered by position, kept balanced by random
+/// heap priorities, where each node knows the size of its subtree and its parent.
+#[derive(Debug, Clone)]
+pub struct IndexedList<T> {
+    nodes: Vec<Node<T>>,
+    root: usize,
+    /// Slots of removed elements, reused by later insertions
+    free: Vec<usize>,
+    seed: u64,
+}
+
+impl<T> Default for IndexedList<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: NIL,
+            free: Vec::new(),
+            seed: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl<T> IndexedList<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+
+    /// Insert `value` so that it ends up at `index`, shifting everything after it along.
+    ///
+    /// Panics if `index` is past the end of the list.
+    pub fn insert(&mut self, index: usize, value: T) -> Handle {
+        assert!(index <= self.len(), "Insert at {index} past the end");
+        let node = self.allocate(value);
+        self.attach(node, index);
+        Handle(node)
+    }
+
+    pub fn push(&mut self, value: T) -> Handle {
+        self.insert(self.len(), value)
+    }
+
+    /// Take the element out of the list. Its handle must not be used again afterwards.
+    pub fn remove(&mut self, handle: Handle) -> T {
+        self.detach(handle.0);
+        self.free.push(handle.0);
+        self.nodes[handle.0]
+            .value
+            .take()
+            .expect("Handle of a removed element")
+    }
+
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        let handle = self.handle_at(index)?;
+        Some(self.remove(handle))
+    }
+
+    /// Move an element so that it ends up at `index`, keeping its handle valid.
+    pub fn move_to(&mut self, handle: Handle, index: usize) {
+        self.detach(handle.0);
+        assert!(index <= self.len(), "Move to {index} past the end");
+        self.attach(handle.0, index);
+    }
+
+    /// The current position of an element.
+    pub fn index_of(&self, handle: Handle) -> usize {
+        let mut node = handle.0;
+        let mut index = self.size(self.nodes[node].left);
+        while self.nodes[node].parent != NIL {
+            let parent = self.nodes[node].parent;
+            if self.nodes[parent].right == node {
+                index += self.size(self.nodes[parent].left) + 1;
+            }
+            node = parent;
+        }
+        index
+    }
+
+    pub fn handle_at(&self, mut index: usize) -> Option<Handle> {
+        let mut node = self.root;
+        while node != NIL {
+            let left = self.size(self.nodes[node].left);
+            match index.cmp(&left) {
+                std::cmp::Ordering::Less => node = self.nodes[node].left,
+                std::cmp::Ordering::Equal => return Some(Handle(node)),
+                std::cmp::Ordering::Greater => {
+                    index -= left + 1;
+                    node = self.nodes[node].right;
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.value(self.handle_at(index)?)
+    }
+
+    /// The element behind a handle, `None` if it has been removed.
+    pub fn value(&self, handle: Handle) -> Option<&T> {
+        self.nodes.get(handle.0)?.value.as_ref()
+    }
+
+    /// The elements in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        let mut stack = Vec::new();
+        let mut node = self.root;
+        std::iter::from_fn(move || {
+            while node != NIL {
+                stack.push(node);
+                node = self.nodes[node].left;
+            }
+            let next = stack.pop()?;
+            node = self.nodes[next].right;
+            self.nodes[next].value.as_ref()
+        })
+    }
+
+    fn allocate(&mut self, value: T) -> usize {
+        // xorshift is plenty random for balancing
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        let node = Node {
+            value: Some(value),
+            priority: self.seed,
+            size: 1,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+        };
+        match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn size(&self, node: usize) -> usize {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node].size
+        }
+    }
+
+    /// Recompute the size of `node` and point its children back at it
+    fn update(&mut self, node: usize) {
+        let Node { left, right, .. } = self.nodes[node];
+        self.nodes[node].size = self.size(left) + self.size(right) + 1;
+        for child in [left, right] {
+            if child != NIL {
+                self.nodes[child].parent = node;
+            }
+        }
+    }
+
+    fn set_root(&mut self, node: usize) {
+        self.root = node;
+        if node != NIL {
+            self.nodes[node].parent = NIL;
+        }
+    }
+
+    /// Split the tree under `node` into its first `count` elements and the rest
+    fn split(&mut self, node: usize, count: usize) -> (usize, usize) {
+        if node == NIL {
+            return (NIL, NIL);
+        }
+        let left = self.nodes[node].left;
+        let left_size = self.size(left);
+        if count <= left_size {
+            let (first, rest) = self.split(left, count);
+            self.nodes[node].left = rest;
+            self.update(node);
+            (first, node)
+        } else {
+            let right = self.nodes[node].right;
+            let (first, rest) = self.split(right, count - left_size - 1);
+            self.nodes[node].right = first;
+            self.update(node);
+            (node, rest)
+        }
+    }
+
+    /// Join two trees, with everything in `first` before everything in `second`
+    fn merge(&mut self, first: usize, second: usize) -> usize {
+        if first == NIL {
+            return second;
+        }
+        if second == NIL {
+            return first;
+        }
+        if self.nodes[first].priority > self.nodes[second].priority {
+            let right = self.nodes[first].right;
+            self.nodes[first].right = self.merge(right, second);
+            self.update(first);
+            first
+        } else {
+            let left = self.nodes[second].left;
+            self.nodes[second].left = self.merge(first, left);
+            self.update(second);
+            second
+        }
+    }
+
+    /// Put a lone node into the tree at `index`
+    fn attach(&mut self, node: usize, index: usize) {
+        let (before, after) = self.split(self.root, index);
+        let joined = self.merge(before, node);
+        let root = self.merge(joined, after);
+        self.set_root(root);
+    }
+
+    /// Take a node out of the tree, leaving it alone
+    fn detach(&mut self, node: usize) {
+        let index = self.index_of(Handle(node));
+        let (before, rest) = self.split(self.root, index);
+        let (alone, after) = self.split(rest, 1);
+        debug_assert_eq!(alone, node);
+        let root = self.merge(before, after);
+        self.set_root(root);
+        let node = &mut self.nodes[node];
+        (node.left, node.right, node.parent, node.size) = (NIL, NIL, NIL, 1);
+    }
+}
+
+impl<T> FromIterator<T> for IndexedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut out = Self::new();
+        for value in iter {
+            out.push(value);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Handle, IndexedList};
+    use proptest::prelude::*;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(usize, u32),
+        Remove(usize),
+        Move(usize, usize),
+    }
+
+    fn ops() -> impl Strategy<Value = Vec<Op>> {
+        let op = prop_oneof![
+            (any::<usize>(), any::<u32>()).prop_map(|(i, v)| Op::Insert(i, v)),
+            any::<usize>().prop_map(Op::Remove),
+            (any::<usize>(), any::<usize>()).prop_map(|(i, j)| Op::Move(i, j)),
+        ];
+        prop::collection::vec(op, 0..200)
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut list: IndexedList<char> = "abcde".chars().collect();
+        let c = list.handle_at(2).unwrap();
+        list.move_to(c, 0);
+        assert_eq!(list.iter().collect::<String>(), "cabde");
+        assert_eq!(list.index_of(c), 0);
+        let z = list.insert(5, 'z');
+        assert_eq!(list.remove_at(1), Some('a'));
+        assert_eq!(list.iter().collect::<String>(), "cbdez");
+        assert_eq!(list.index_of(z), 4);
+        assert_eq!(list.remove(c), 'c');
+        assert_eq!(list.value(c), None);
+        assert_eq!(list.get(0), Some(&'b'));
+        assert_eq!(list.get(4), None);
+        assert_eq!(list.len(), 4);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_vec(ops in ops()) {
+            let mut list = IndexedList::new();
+            let mut model: Vec<(Handle, u32)> = Vec::new();
+            for op in ops {
+                match op {
+                    Op::Insert(i, v) => {
+                        let i = i % (model.len() + 1);
+                        let handle = list.insert(i, v);
+                        model.insert(i, (handle, v));
+                    }
+                    Op::Remove(i) if !model.is_empty() => {
+                        let (handle, v) = model.remove(i % model.len());
+                        prop_assert_eq!(list.remove(handle), v);
+                    }
+                    Op::Move(i, j) if !model.is_empty() => {
+                        let entry = model.remove(i % model.len());
+                        let j = j % (model.len() + 1);
+                        list.move_to(entry.0, j);
+                        model.insert(j, entry);
+                    }
+                    _ => {}
+                }
+                prop_assert_eq!(list.len(), model.len());
+            }
+            let values: Vec<u32> = list.iter().copied().collect();
+            prop_assert_eq!(values, model.iter().map(|&(_, v)| v).collect::<Vec<_>>());
+            for (i, &(handle, v)) in model.iter().enumerate() {
+                prop_assert_eq!(list.index_of(handle), i);
+                prop_assert_eq!(list.handle_at(i), Some(handle));
+                prop_assert_eq!(list.get(i), Some(&v));
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::point2d::{max, min, Coordinate};
+use std::ops::RangeInclusive;
+
+/// A set of integers stored as sorted, disjoint and non-adjacent inclusive intervals.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct IntervalSet<T> {
+    intervals: Vec<(T, T)>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self {
+            intervals: Vec::new(),
+        }
+    }
+}
+
+impl<T: Coordinate> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The number of integers in the set.
+    pub fn len(&self) -> T {
+        self.intervals.iter().fold(T::zero(), |acc, &(start, end)| {
+            acc + (end - start) + T::one()
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.intervals.iter().map(|&(start, end)| start..=end)
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        // First interval that ends at or after value, the set contains value iff that one starts before it
+        let idx = self.intervals.partition_point(|&(_, end)| end < value);
+        self.intervals
+            .get(idx)
+            .is_some_and(|&(start, _)| start <= value)
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let (mut start, mut end) = range.into_inner();
+        if end < start {
+            return;
+        }
+        // Intervals that end before start - 1 are untouched on the left, and the ones
+        // starting after end + 1 on the right, everything between gets merged
+        let left = self
+            .intervals
+            .partition_point(|&(_, e)| e + T::one() < start);
+        let right = self
+            .intervals
+            .partition_point(|&(s, _)| s <= end + T::one());
+        if left < right {
+            start = min(start, self.intervals[left].0);
+            end = max(end, self.intervals[right - 1].1);
+        }
+        self.intervals.splice(left..right, [(start, end)]);
+    }
+
+    pub fn remove(&mut self, range: RangeInclusive<T>) {
+        let (start, end) = range.into_inner();
+        if end < start {
+            return;
+        }
+        let left = self.intervals.partition_point(|&(_, e)| e < start);
+        let right = self.intervals.partition_point(|&(s, _)| s <= end);
+        let mut remaining = Vec::with_capacity(2);
+        if left < right {
+            let (first_start, _) = self.intervals[left];
+            let (_, last_end) = self.intervals[right - 1];
+            if first_start < start {
+                remaining.push((first_start, start - T::one()));
+            }
+            if last_end > end {
+                remaining.push((end + T::one(), last_end));
+            }
+        }
+        self.intervals.splice(left..right, remaining);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        for range in other.iter() {
+            out.insert(range);
+        }
+        out
+    }
+
+    pub fn subtract(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        for range in other.iter() {
+            out.remove(range);
+        }
+        out
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.subtract(&self.subtract(other))
+    }
+
+    /// Everything in `bounds` that is not in the set.
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> Self {
+        let mut out: Self = std::iter::once(bounds).collect();
+        for range in self.iter() {
+            out.remove(range);
+        }
+        out
+    }
+}
+
+impl<T: Coordinate> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        let mut out = Self::new();
+        for range in iter {
+            out.insert(range);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntervalSet;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+    use std::ops::RangeInclusive;
+
+    fn ranges() -> impl Strategy<Value = Vec<RangeInclusive<i32>>> {
+        prop::collection::vec((-50i32..50, 0i32..10).prop_map(|(s, l)| s..=s + l), 0..10)
+    }
+
+    fn naive(ranges: &[RangeInclusive<i32>]) -> BTreeSet<i32> {
+        ranges.iter().flat_map(|r| r.clone()).collect()
+    }
+
+    fn members(set: &IntervalSet<i32>) -> BTreeSet<i32> {
+        set.iter().flatten().collect()
+    }
+
+    #[test]
+    fn test_merges_adjacent() {
+        let set: IntervalSet<i32> = vec![1..=3, 4..=5, 8..=9].into_iter().collect();
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1..=5, 8..=9]);
+        assert_eq!(set.len(), 7);
+        assert_eq!(
+            set.complement(0..=10).iter().collect::<Vec<_>>(),
+            vec![0..=0, 6..=7, 10..=10]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_insert(rs in ranges()) {
+            let set: IntervalSet<i32> = rs.iter().cloned().collect();
+            let expected = naive(&rs);
+            prop_assert_eq!(members(&set), expected.clone());
+            prop_assert_eq!(set.len() as usize, expected.len());
+            prop_assert!(set.iter().collect::<Vec<_>>().windows(2).all(|w| w[0].end() + 1 < *w[1].start()));
+            for v in -60..70 {
+                prop_assert_eq!(set.contains(v), expected.contains(&v));
+            }
+        }
+
+        #[test]
+        fn prop_set_operations(a in ranges(), b in ranges()) {
+            let sa: IntervalSet<i32> = a.iter().cloned().collect();
+            let sb: IntervalSet<i32> = b.iter().cloned().collect();
+            let (na, nb) = (naive(&a), naive(&b));
+            prop_assert_eq!(members(&sa.union(&sb)), na.union(&nb).copied().collect());
+            prop_assert_eq!(members(&sa.subtract(&sb)), na.difference(&nb).copied().collect());
+            prop_assert_eq!(members(&sa.intersection(&sb)), na.intersection(&nb).copied().collect());
+        }
+
+        #[test]
+        fn prop_complement(a in ranges(), lo in -60i32..0, hi in 0i32..60) {
+            let sa: IntervalSet<i32> = a.iter().cloned().collect();
+            let na = naive(&a);
+            let expected: BTreeSet<i32> = (lo..=hi).filter(|v| !na.contains(v)).collect();
+            prop_assert_eq!(members(&sa.complement(lo..=hi)), expected);
+        }
+    }
+}
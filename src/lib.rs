@@ -10,104 +10,77 @@ pub mod io {
 }
 
 pub mod point2d {
+    use num_traits::{Euclid, Float, Num, Signed};
     use std::ops::{Add, Div, Mul, Sub};
 
+    /// Numeric types usable as coordinates: anything with the usual arithmetic, zero and one.
+    pub trait Coordinate: Num + Copy + PartialOrd {}
+    impl<T: Num + Copy + PartialOrd> Coordinate for T {}
+
     #[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Copy, Hash)]
     pub struct Point2d<T> {
         pub x: T,
         pub y: T,
     }
 
-    pub trait Abs<T>
-    where
-        T: Copy,
-    {
-        fn abs(self) -> Self;
-    }
-    impl Abs<i32> for i32 {
-        fn abs(self) -> Self {
-            self.abs()
-        }
-    }
-    impl Abs<i64> for i64 {
-        fn abs(self) -> Self {
-            self.abs()
-        }
-    }
-    impl Abs<f32> for f32 {
-        fn abs(self) -> Self {
-            self.abs()
-        }
-    }
-    impl Abs<f64> for f64 {
-        fn abs(self) -> Self {
-            self.abs()
-        }
-    }
-    pub trait Sqrt<T> {
-        fn sqrt(self) -> Self;
+    #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+    pub struct Rect<T> {
+        pub northwest: Point2d<T>,
+        pub southeast: Point2d<T>,
     }
-    impl Sqrt<f64> for f64 {
-        fn sqrt(self: f64) -> Self {
-            self.sqrt()
+
+    pub(crate) fn min<T: PartialOrd>(a: T, b: T) -> T {
+        if b < a {
+            b
+        } else {
+            a
         }
     }
-    impl Sqrt<f32> for f32 {
-        fn sqrt(self: f32) -> Self {
-            self.sqrt()
+
+    pub(crate) fn max<T: PartialOrd>(a: T, b: T) -> T {
+        if b > a {
+            b
+        } else {
+            a
         }
     }
 
-    #[derive(Debug, Eq, PartialEq, Hash)]
-    pub struct Rect<T> {
-        pub northwest: Point2d<T>,
-        pub southeast: Point2d<T>,
-    }
-    impl<
-            T: PartialOrd<T> + Copy + From<i32> + Sub<Output = T> + Add<Output = T> + Mul<Output = T>,
-        > Rect<T>
-    {
+    impl<T: Coordinate> Rect<T> {
         pub fn contains(&self, point: Point2d<T>) -> bool {
             (self.northwest.x..=self.southeast.x).contains(&point.x)
                 && (self.northwest.y..=self.southeast.y).contains(&point.y)
         }
         pub fn area(&self) -> T {
-            let x = self.southeast.x - self.northwest.x + 1.into();
-            let y = self.southeast.y - self.northwest.y + 1.into();
+            let (x, y) = self.dims();
             x * y
         }
         pub fn dims(&self) -> (T, T) {
-            let xdim = (self.southeast.x - self.northwest.x) + 1.into();
-            let ydim = (self.southeast.y - self.northwest.y) + 1.into();
+            let xdim = (self.southeast.x - self.northwest.x) + T::one();
+            let ydim = (self.southeast.y - self.northwest.y) + T::one();
             (xdim, ydim)
         }
-        pub fn bound(points: impl Iterator<Item = Point2d<T>>) -> Rect<T> {
-            let bounds = points.fold(((0.into(), 0.into()), (0.into(), 0.into())), |b, p| {
-                let (xmin, xmax) = b.0;
-                let (ymin, ymax) = b.1;
-
-                (
-                    if p.x < xmin {
-                        (p.x, xmax)
-                    } else if p.x > xmax {
-                        (xmin, p.x)
-                    } else {
-                        (xmin, xmax)
-                    },
-                    if p.y < ymin {
-                        (p.y, ymax)
-                    } else if p.y > ymax {
-                        (ymin, p.y)
-                    } else {
-                        (ymin, ymax)
-                    },
-                )
-            });
+        /// Grow the rectangle just enough to contain `point`.
+        pub fn extend(self, point: Point2d<T>) -> Self {
             Self {
-                northwest: (bounds.0 .0, bounds.1 .0).into(),
-                southeast: (bounds.0 .1, bounds.1 .1).into(),
+                northwest: Point2d::new(
+                    min(self.northwest.x, point.x),
+                    min(self.northwest.y, point.y),
+                ),
+                southeast: Point2d::new(
+                    max(self.southeast.x, point.x),
+                    max(self.southeast.y, point.y),
+                ),
             }
         }
+        /// The smallest rectangle containing every point, or `None` if there are no points.
+        pub fn bound(mut points: impl Iterator<Item = Point2d<T>>) -> Option<Rect<T>> {
+            let first = points.next()?;
+            let start = Rect {
+                northwest: first,
+                southeast: first,
+            };
+            Some(points.fold(start, Rect::extend))
+        }
     }
 
     impl<T> From<(T, T)> for Point2d<T> {
@@ -116,109 +89,67 @@ pub mod point2d {
         }
     }
 
-    pub trait Rem<T> {
-        fn rem_euclid(self, divisor: T) -> T;
-    }
-    impl Rem<i32> for i32 {
-        fn rem_euclid(self, divisor: i32) -> i32 {
-            self.rem_euclid(divisor)
-        }
-    }
-    impl Rem<i64> for i64 {
-        fn rem_euclid(self, divisor: i64) -> i64 {
-            self.rem_euclid(divisor)
-        }
-    }
-    impl Rem<usize> for usize {
-        fn rem_euclid(self, divisor: usize) -> usize {
-            self.rem_euclid(divisor)
-        }
-    }
-    impl Rem<u32> for u32 {
-        fn rem_euclid(self, divisor: u32) -> u32 {
-            self.rem_euclid(divisor)
-        }
-    }
-    impl Rem<u64> for u64 {
-        fn rem_euclid(self, divisor: u64) -> u64 {
-            self.rem_euclid(divisor)
-        }
-    }
-
-    impl<T> Point2d<T>
-    where
-        T: Rem<T>,
-    {
+    impl<T: Coordinate + Euclid> Point2d<T> {
         pub fn wrap(self, xmax: T, ymax: T) -> Self {
             Self {
-                x: self.x.rem_euclid(xmax),
-                y: self.y.rem_euclid(ymax),
+                x: self.x.rem_euclid(&xmax),
+                y: self.y.rem_euclid(&ymax),
             }
         }
     }
 
-    impl<T> Point2d<T>
-    where
-        T: Add<Output = T>
-            + Mul<Output = T>
-            + Copy
-            + Abs<T>
-            + Sub<Output = T>
-            + Div<Output = T>
-            + From<i32>
-            + PartialOrd<T>,
-    {
+    impl<T: Coordinate> Point2d<T> {
         pub fn origin() -> Self {
-            Self::new(0.into(), 0.into())
+            Self::new(T::zero(), T::zero())
         }
 
         pub fn northwest(self) -> Self {
             Self {
-                x: self.x - 1.into(),
-                y: self.y - 1.into(),
+                x: self.x - T::one(),
+                y: self.y - T::one(),
             }
         }
 
         pub fn north(self) -> Self {
             Self {
                 x: self.x,
-                y: self.y - 1.into(),
+                y: self.y - T::one(),
             }
         }
         pub fn northeast(self) -> Self {
             Self {
-                x: self.x + 1.into(),
-                y: self.y - 1.into(),
+                x: self.x + T::one(),
+                y: self.y - T::one(),
             }
         }
         pub fn west(self) -> Self {
             Self {
-                x: self.x - 1.into(),
+                x: self.x - T::one(),
                 y: self.y,
             }
         }
         pub fn southwest(self) -> Self {
             Self {
-                x: self.x - 1.into(),
-                y: self.y + 1.into(),
+                x: self.x - T::one(),
+                y: self.y + T::one(),
             }
         }
         pub fn east(self) -> Self {
             Self {
-                x: self.x + 1.into(),
+                x: self.x + T::one(),
                 y: self.y,
             }
         }
         pub fn southeast(self) -> Self {
             Self {
-                x: self.x + 1.into(),
-                y: self.y + 1.into(),
+                x: self.x + T::one(),
+                y: self.y + T::one(),
             }
         }
         pub fn south(self) -> Self {
             Self {
                 x: self.x,
-                y: self.y + 1.into(),
+                y: self.y + T::one(),
             }
         }
         pub fn around(self) -> Vec<Self> {
@@ -239,20 +170,8 @@ pub mod point2d {
 
         pub fn clamp(self, xbound: (T, T), ybound: (T, T)) -> Self {
             Self {
-                x: if self.x < xbound.0 {
-                    xbound.0
-                } else if self.x > xbound.1 {
-                    xbound.1
-                } else {
-                    self.x
-                },
-                y: if self.y < ybound.0 {
-                    ybound.0
-                } else if self.y > ybound.1 {
-                    ybound.1
-                } else {
-                    self.y
-                },
+                x: max(xbound.0, min(self.x, xbound.1)),
+                y: max(ybound.0, min(self.y, ybound.1)),
             }
         }
 
@@ -265,23 +184,30 @@ pub mod point2d {
         pub fn invscale(&self, divisor: T) -> Self {
             Self::new(self.x / divisor, self.y / divisor)
         }
-        pub fn abs(self) -> Point2d<T> {
-            Self::new(self.x.abs(), self.y.abs())
-        }
         pub fn sum(self) -> T {
             self.x + self.y
         }
+        pub fn square(self) -> Self {
+            self * self
+        }
+    }
+
+    impl<T: Coordinate + Signed> Point2d<T> {
+        pub fn abs(self) -> Point2d<T> {
+            Self::new(self.x.abs(), self.y.abs())
+        }
         pub fn manhattan(self) -> T {
             self.abs().sum()
         }
-        pub fn square(self) -> Self {
-            self * self
+        /// Each coordinate replaced by -1, 0 or 1, a step of at most one in each direction.
+        pub fn signum(self) -> Point2d<T> {
+            Self::new(self.x.signum(), self.y.signum())
         }
     }
 
-    impl<T: Sqrt<T> + Add<Output = T>> Point2d<T> {
+    impl<T: Coordinate + Float> Point2d<T> {
         pub fn euclid(self) -> T {
-            (self.x + self.y).sqrt()
+            self.x.hypot(self.y)
         }
     }
 
@@ -328,4 +254,124 @@ pub mod point2d {
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Point2d, Rect};
+        use proptest::prelude::*;
+
+        fn point() -> impl Strategy<Value = Point2d<i64>> {
+            (-1000i64..1000, -1000i64..1000).prop_map(Point2d::from)
+        }
+
+        #[test]
+        fn test_bound_empty() {
+            assert_eq!(Rect::<i64>::bound(std::iter::empty()), None);
+        }
+
+        #[test]
+        fn test_bound_excludes_origin() {
+            let rect = Rect::bound(vec![(5, 7).into(), (8, 6).into()].into_iter()).unwrap();
+            assert_eq!(rect.northwest, (5, 6).into());
+            assert_eq!(rect.southeast, (8, 7).into());
+            assert_eq!(rect.area(), 8);
+        }
+
+        #[test]
+        fn test_euclid() {
+            assert_eq!(Point2d::new(3.0, 4.0).euclid(), 5.0);
+            assert_eq!(Point2d::new(-3.0f32, 4.0).euclid(), 5.0);
+        }
+
+        #[test]
+        fn test_signum() {
+            assert_eq!(Point2d::new(-7, 0).signum(), Point2d::new(-1, 0));
+            assert_eq!(Point2d::new(3, -2).signum(), Point2d::new(1, -1));
+        }
+
+        proptest! {
+            #[test]
+            fn prop_add_sub_inverse(a in point(), b in point()) {
+                prop_assert_eq!(a + b - b, a);
+                prop_assert_eq!(a + b, b + a);
+            }
+
+            #[test]
+            fn prop_mul_div(a in point(), k in 1i64..100) {
+                prop_assert_eq!(a.scale(k).invscale(k), a);
+                prop_assert_eq!(a.scale(k) / Point2d::new(k, k), a);
+                prop_assert_eq!(a.square(), Point2d::new(a.x * a.x, a.y * a.y));
+            }
+
+            #[test]
+            fn prop_shift(a in point(), b in point()) {
+                let mut c = a;
+                c.shift(&b);
+                prop_assert_eq!(c, a + b);
+            }
+
+            #[test]
+            fn prop_around(a in point()) {
+                let around = a.around();
+                prop_assert_eq!(around.len(), 8);
+                prop_assert!(around.iter().all(|&n| (n - a).abs().x <= 1 && (n - a).abs().y <= 1 && n != a));
+                prop_assert_eq!(a.north().south(), a);
+                prop_assert_eq!(a.east().west(), a);
+                prop_assert_eq!(a.northwest().southeast(), a);
+                prop_assert_eq!(a.northeast().southwest(), a);
+            }
+
+            #[test]
+            fn prop_manhattan(a in point(), b in point()) {
+                prop_assert_eq!(a.manhattan(), a.x.abs() + a.y.abs());
+                prop_assert!((a + b).manhattan() <= a.manhattan() + b.manhattan());
+            }
+
+            #[test]
+            fn prop_euclid(x in -1000.0f64..1000.0, y in -1000.0f64..1000.0) {
+                let p = Point2d::new(x, y);
+                prop_assert!((p.euclid() - (x * x + y * y).sqrt()).abs() < 1e-9);
+                prop_assert!(p.euclid() <= p.manhattan() + 1e-9);
+            }
+
+            #[test]
+            fn prop_wrap(a in point(), w in 1i64..50, h in 1i64..50) {
+                let wrapped = a.wrap(w, h);
+                prop_assert!((0..w).contains(&wrapped.x));
+                prop_assert!((0..h).contains(&wrapped.y));
+                prop_assert_eq!((wrapped.x - a.x) % w, 0);
+                prop_assert_eq!((wrapped.y - a.y) % h, 0);
+            }
+
+            #[test]
+            fn prop_clamp(a in point(), lo in -500i64..0, hi in 0i64..500) {
+                let c = a.clamp((lo, hi), (lo, hi));
+                prop_assert!((lo..=hi).contains(&c.x) && (lo..=hi).contains(&c.y));
+                if (lo..=hi).contains(&a.x) && (lo..=hi).contains(&a.y) {
+                    prop_assert_eq!(c, a);
+                }
+            }
+
+            #[test]
+            fn prop_bound(points in prop::collection::vec(point(), 1..50)) {
+                let rect = Rect::bound(points.iter().copied()).unwrap();
+                prop_assert!(points.iter().all(|&p| rect.contains(p)));
+                // Tight: every edge touches at least one point
+                prop_assert!(points.iter().any(|p| p.x == rect.northwest.x));
+                prop_assert!(points.iter().any(|p| p.x == rect.southeast.x));
+                prop_assert!(points.iter().any(|p| p.y == rect.northwest.y));
+                prop_assert!(points.iter().any(|p| p.y == rect.southeast.y));
+                let (w, h) = rect.dims();
+                prop_assert_eq!(rect.area(), w * h);
+                prop_assert!(w >= 1 && h >= 1);
+            }
+        }
+    }
 }
+
+pub mod point3d;
+pub mod pointn;
+pub mod interval;
+pub mod ocr;
+pub mod indexed_list;
+pub mod balanced;
@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+
+/// Glyphs are 4 pixels wide and 6 pixels tall, with a blank column between letters
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+const GLYPH_PITCH: usize = GLYPH_WIDTH + 1;
+
+/// The letters known to show up in puzzles drawing text, row by row
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Read the letters drawn in `display`, where `#` is a lit pixel and anything else is dark.
+///
+/// Each letter is expected in its own 5 column cell, blank cells are read as spaces.
+pub fn parse_letters(display: &str) -> Result<String> {
+    let rows: Vec<Vec<bool>> = display
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().map(|ch| ch == '#').collect())
+        .collect();
+    if rows.len() != GLYPH_HEIGHT {
+        return Err(anyhow!(
+            "Expected {GLYPH_HEIGHT} rows of pixels, found {}",
+            rows.len()
+        ));
+    }
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let pixel = |x: usize, y: usize| rows[y].get(x).copied().unwrap_or(false);
+
+    let mut letters = String::new();
+    for start in (0..width).step_by(GLYPH_PITCH) {
+        let glyph: Vec<String> = (0..GLYPH_HEIGHT)
+            .map(|y| {
+                (start..start + GLYPH_WIDTH)
+                    .map(|x| if pixel(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        if glyph.iter().all(|row| !row.contains('#')) {
+            letters.push(' ');
+            continue;
+        }
+        if (0..GLYPH_HEIGHT).any(|y| pixel(start + GLYPH_WIDTH, y)) {
+            return Err(anyhow!(
+                "Expected a blank column between letters at column {}",
+                start + GLYPH_WIDTH
+            ));
+        }
+        let letter = FONT
+            .iter()
+            .find(|(_, rows)| rows.iter().zip(glyph.iter()).all(|(a, b)| a == b))
+            .map(|(letter, _)| *letter)
+            .with_context(|| format!("Unknown glyph at column {start}:\n{}", glyph.join("\n")))?;
+        letters.push(letter);
+    }
+    Ok(letters.trim_end().to_string())
+}
+
+/// Draw `text` with `#` for lit and `.` for dark pixels, the inverse of `parse_letters`.
+pub fn render_letters(text: &str) -> Result<String> {
+    let glyphs = text
+        .chars()
+        .map(|ch| {
+            if ch == ' ' {
+                return Ok(["...."; GLYPH_HEIGHT]);
+            }
+            FONT.iter()
+                .find(|(letter, _)| *letter == ch)
+                .map(|(_, rows)| *rows)
+                .with_context(|| format!("No glyph for {ch:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let rows: Vec<String> = (0..GLYPH_HEIGHT)
+        .map(|y| {
+            glyphs
+                .iter()
+                .map(|glyph| format!("{}.", glyph[y]))
+                .collect()
+        })
+        .collect();
+    Ok(rows.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_letters, render_letters, FONT};
+
+    #[test]
+    fn test_parse_rendered_font() {
+        let alphabet: String = FONT.iter().map(|(letter, _)| *letter).collect();
+        let display = render_letters(&alphabet).unwrap();
+        assert_eq!(parse_letters(&display).unwrap(), alphabet);
+    }
+
+    #[test]
+    fn test_parse_crt_style() {
+        // 40 column wide display with spaces for dark pixels
+        let display = "\
+###  #### #  # #### #### #### #  #  ##
+#  # #    # #     # #    #    #  # #  #
+#  # ###  ##     #  ###  ###  #### #
+###  #    # #   #   #    #    #  # # ##
+#    #    # #  #    #    #    #  # #  #
+#    #### #  # #### #    #### #  #  ### ";
+        assert_eq!(parse_letters(display).unwrap(), "PEKZFEHG");
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let display = "#..#.\n.##..\n#..#.\n#..#.\n.##..\n#..#.";
+        let err = parse_letters(display).unwrap_err();
+        assert!(err.to_string().contains("Unknown glyph at column 0"));
+        assert!(parse_letters("#\n#").is_err());
+        assert!(render_letters("Q").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::point2d::{max, min, Coordinate};
+use num_traits::Signed;
+use std::ops::{Add, Div, Mul, Sub};
+
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Point3d<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T> From<(T, T, T)> for Point3d<T> {
+    fn from(t: (T, T, T)) -> Self {
+        Self {
+            x: t.0,
+            y: t.1,
+            z: t.2,
+        }
+    }
+}
+
+impl<T: Coordinate> Point3d<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Point3d { x, y, z }
+    }
+    pub fn origin() -> Self {
+        Self::new(T::zero(), T::zero(), T::zero())
+    }
+    pub fn scale(&self, factor: T) -> Self {
+        Self::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+    pub fn invscale(&self, divisor: T) -> Self {
+        Self::new(self.x / divisor, self.y / divisor, self.z / divisor)
+    }
+    pub fn sum(self) -> T {
+        self.x + self.y + self.z
+    }
+
+    /// Every point that differs from `self` by at most one along each axis, where at most
+    /// `max_changed` axes differ. `1` gives the 6 face neighbours, `2` adds the 12 edge
+    /// neighbours and `3` adds the 8 corners.
+    fn connected(self, max_changed: usize) -> impl Iterator<Item = Self> {
+        let steps = |v: T| [(v - T::one(), 1), (v, 0), (v + T::one(), 1)];
+        let (xs, ys, zs) = (steps(self.x), steps(self.y), steps(self.z));
+        xs.into_iter()
+            .flat_map(move |x| ys.into_iter().map(move |y| (x, y)))
+            .flat_map(move |(x, y)| zs.into_iter().map(move |z| (x, y, z)))
+            .filter(move |((_, dx), (_, dy), (_, dz))| {
+                let changed = dx + dy + dz;
+                changed > 0 && changed <= max_changed
+            })
+            .map(|((x, _), (y, _), (z, _))| Self::new(x, y, z))
+    }
+    /// The 6 points sharing a face with this one.
+    pub fn faces(self) -> impl Iterator<Item = Self> {
+        self.connected(1)
+    }
+    /// The 18 points sharing a face or an edge with this one.
+    pub fn faces_and_edges(self) -> impl Iterator<Item = Self> {
+        self.connected(2)
+    }
+    /// All 26 points touching this one.
+    pub fn around(self) -> impl Iterator<Item = Self> {
+        self.connected(3)
+    }
+}
+
+impl<T: Coordinate + Signed> Point3d<T> {
+    pub fn abs(self) -> Self {
+        Self::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+    pub fn manhattan(self) -> T {
+        self.abs().sum()
+    }
+}
+
+impl<T: Add<Output = T>> Add for Point3d<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Point3d<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl<T: Mul<Output = T>> Mul for Point3d<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+            z: self.z * rhs.z,
+        }
+    }
+}
+
+impl<T: Div<Output = T>> Div for Point3d<T> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z,
+        }
+    }
+}
+
+/// An axis aligned box, inclusive in both corners.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct Cuboid<T> {
+    pub min: Point3d<T>,
+    pub max: Point3d<T>,
+}
+
+impl<T: Coordinate> Cuboid<T> {
+    pub fn contains(&self, point: Point3d<T>) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+    pub fn dims(&self) -> (T, T, T) {
+        let d = self.max - self.min;
+        (d.x + T::one(), d.y + T::one(), d.z + T::one())
+    }
+    pub fn volume(&self) -> T {
+        let (x, y, z) = self.dims();
+        x * y * z
+    }
+    /// Grow the cuboid just enough to contain `point`.
+    pub fn extend(self, point: Point3d<T>) -> Self {
+        Self {
+            min: Point3d::new(
+                min(self.min.x, point.x),
+                min(self.min.y, point.y),
+                min(self.min.z, point.z),
+            ),
+            max: Point3d::new(
+                max(self.max.x, point.x),
+                max(self.max.y, point.y),
+                max(self.max.z, point.z),
+            ),
+        }
+    }
+    /// Grow the cuboid by `margin` in every direction.
+    pub fn pad(self, margin: T) -> Self {
+        let margin = Point3d::new(margin, margin, margin);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+    /// The smallest cuboid containing every point, or `None` if there are no points.
+    pub fn bound(mut points: impl Iterator<Item = Point3d<T>>) -> Option<Self> {
+        let first = points.next()?;
+        let start = Cuboid {
+            min: first,
+            max: first,
+        };
+        Some(points.fold(start, Cuboid::extend))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cuboid, Point3d};
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn point() -> impl Strategy<Value = Point3d<i64>> {
+        (-1000i64..1000, -1000i64..1000, -1000i64..1000).prop_map(Point3d::from)
+    }
+
+    #[test]
+    fn test_bound_empty() {
+        assert_eq!(Cuboid::<i64>::bound(std::iter::empty()), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_add_sub_inverse(a in point(), b in point()) {
+            prop_assert_eq!(a + b - b, a);
+            prop_assert_eq!(a + b, b + a);
+        }
+
+        #[test]
+        fn prop_scale(a in point(), k in 1i64..100) {
+            prop_assert_eq!(a.scale(k).invscale(k), a);
+            prop_assert_eq!(a * Point3d::new(k, k, k) / Point3d::new(k, k, k), a);
+        }
+
+        #[test]
+        fn prop_neighbours(a in point()) {
+            let faces: HashSet<_> = a.faces().collect();
+            let edges: HashSet<_> = a.faces_and_edges().collect();
+            let around: HashSet<_> = a.around().collect();
+            prop_assert_eq!(faces.len(), 6);
+            prop_assert_eq!(edges.len(), 18);
+            prop_assert_eq!(around.len(), 26);
+            prop_assert!(faces.iter().all(|&n| (n - a).manhattan() == 1));
+            prop_assert!(edges.iter().all(|&n| (1..=2).contains(&(n - a).manhattan())));
+            prop_assert!(faces.is_subset(&edges) && edges.is_subset(&around));
+            prop_assert!(!around.contains(&a));
+        }
+
+        #[test]
+        fn prop_manhattan(a in point(), b in point()) {
+            prop_assert_eq!(a.manhattan(), a.x.abs() + a.y.abs() + a.z.abs());
+            prop_assert!((a + b).manhattan() <= a.manhattan() + b.manhattan());
+        }
+
+        #[test]
+        fn prop_bound(points in prop::collection::vec(point(), 1..50), margin in 0i64..3) {
+            let cuboid = Cuboid::bound(points.iter().copied()).unwrap();
+            prop_assert!(points.iter().all(|&p| cuboid.contains(p)));
+            prop_assert!(points.iter().any(|p| p.x == cuboid.min.x));
+            prop_assert!(points.iter().any(|p| p.y == cuboid.max.y));
+            prop_assert!(points.iter().any(|p| p.z == cuboid.min.z));
+            let (x, y, z) = cuboid.dims();
+            prop_assert_eq!(cuboid.volume(), x * y * z);
+            let padded = cuboid.pad(margin);
+            let (px, py, pz) = padded.dims();
+            prop_assert_eq!((px, py, pz), (x + 2 * margin, y + 2 * margin, z + 2 * margin));
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::point2d::{Coordinate, Point2d};
+use crate::point3d::Point3d;
+use num_traits::Signed;
+use std::ops::{Add, Sub};
+
+/// A point with `N` coordinates, for puzzles that don't fit in two or three dimensions.
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct PointN<T, const N: usize>(pub [T; N]);
+
+impl<T, const N: usize> From<[T; N]> for PointN<T, N> {
+    fn from(coords: [T; N]) -> Self {
+        Self(coords)
+    }
+}
+
+impl<T> From<Point2d<T>> for PointN<T, 2> {
+    fn from(p: Point2d<T>) -> Self {
+        Self([p.x, p.y])
+    }
+}
+
+impl<T> From<Point3d<T>> for PointN<T, 3> {
+    fn from(p: Point3d<T>) -> Self {
+        Self([p.x, p.y, p.z])
+    }
+}
+
+impl<T: Coordinate, const N: usize> PointN<T, N> {
+    pub fn origin() -> Self {
+        Self([T::zero(); N])
+    }
+    pub fn sum(self) -> T {
+        self.0.into_iter().fold(T::zero(), |acc, c| acc + c)
+    }
+    pub fn scale(&self, factor: T) -> Self {
+        Self(self.0.map(|c| c * factor))
+    }
+    /// The `2 * N` points that differ by one along a single axis.
+    pub fn faces(self) -> impl Iterator<Item = Self> {
+        (0..N).flat_map(move |axis| {
+            let mut down = self;
+            let mut up = self;
+            down.0[axis] = down.0[axis] - T::one();
+            up.0[axis] = up.0[axis] + T::one();
+            [down, up]
+        })
+    }
+    /// All `3^N - 1` points touching this one.
+    pub fn around(self) -> impl Iterator<Item = Self> {
+        let total = 3usize.pow(N as u32);
+        (0..total)
+            .filter(move |&code| code != total / 2)
+            .map(move |mut code| {
+                let mut point = self;
+                for c in point.0.iter_mut() {
+                    match code % 3 {
+                        0 => *c = *c - T::one(),
+                        2 => *c = *c + T::one(),
+                        _ => {}
+                    }
+                    code /= 3;
+                }
+                point
+            })
+    }
+}
+
+impl<T: Coordinate + Signed, const N: usize> PointN<T, N> {
+    pub fn abs(self) -> Self {
+        Self(self.0.map(|c| c.abs()))
+    }
+    pub fn manhattan(self) -> T {
+        self.abs().sum()
+    }
+}
+
+impl<T: Coordinate, const N: usize> Add for PointN<T, N> {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        for (c, r) in self.0.iter_mut().zip(rhs.0) {
+            *c = *c + r;
+        }
+        self
+    }
+}
+
+impl<T: Coordinate, const N: usize> Sub for PointN<T, N> {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        for (c, r) in self.0.iter_mut().zip(rhs.0) {
+            *c = *c - r;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PointN;
+    use crate::point3d::Point3d;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn point() -> impl Strategy<Value = PointN<i64, 4>> {
+        prop::array::uniform4(-1000i64..1000).prop_map(PointN)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_add_sub_inverse(a in point(), b in point()) {
+            prop_assert_eq!(a + b - b, a);
+            prop_assert_eq!(a + b, b + a);
+        }
+
+        #[test]
+        fn prop_neighbours(a in point()) {
+            let faces: HashSet<_> = a.faces().collect();
+            let around: HashSet<_> = a.around().collect();
+            prop_assert_eq!(faces.len(), 8);
+            prop_assert_eq!(around.len(), 80);
+            prop_assert!(faces.iter().all(|&n| (n - a).manhattan() == 1));
+            prop_assert!(faces.is_subset(&around));
+            prop_assert!(!around.contains(&a));
+        }
+
+        #[test]
+        fn prop_matches_point3d(x in -100i32..100, y in -100i32..100, z in -100i32..100) {
+            let p = Point3d::new(x, y, z);
+            let n: PointN<i32, 3> = p.into();
+            prop_assert_eq!(n.manhattan(), p.manhattan());
+            let faces: HashSet<PointN<i32, 3>> = p.faces().map(PointN::from).collect();
+            prop_assert_eq!(faces, n.faces().collect::<HashSet<_>>());
+            let around: HashSet<PointN<i32, 3>> = p.around().map(PointN::from).collect();
+            prop_assert_eq!(around, n.around().collect::<HashSet<_>>());
+        }
+    }
+}